@@ -0,0 +1,227 @@
+//! What lies beyond the edges of the grid, written the way Golly writes bounded grids: `T` for a
+//! torus, `P` for a plane with dead cells outside, `K` for a Klein bottle, `C` for a cross-surface
+//! and `S` for a sphere, optionally followed by the grid size as in `T30,20`. A plane of size zero,
+//! `P0,0`, has no edges at all.
+
+use anyhow::{bail, ensure, Context, Result};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    /// Opposite edges are joined. A pattern leaving through the top or bottom edge comes back
+    /// `shift[0]` cells further right, one leaving through a side edge `shift[1]` cells further
+    /// up; at most one of them is non-zero.
+    Torus { shift: [i32; 2] },
+    /// Cells outside the grid are dead.
+    Plane,
+    /// Like the torus, but the top and bottom edges (or the left and right edges if
+    /// `twisted_sides`) are joined with a twist that mirrors what crosses them.
+    KleinBottle { twisted_sides: bool },
+    /// Both pairs of opposite edges are joined with a twist.
+    CrossSurface,
+    /// The top edge is joined to the left edge and the bottom edge to the right one; the grid must
+    /// be square.
+    Sphere,
+    /// An infinite plane, stored as the chunks around live cells; the grid size is only the size
+    /// of the initial soup and of the view.
+    Unbounded,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Boundary::Torus { shift: [0, 0] }
+    }
+}
+
+impl Boundary {
+    /// Whether the boundary only ever moves cells by whole grid sizes, so any lattice lines up
+    /// with itself across it.
+    pub fn preserves_lattice(self) -> bool {
+        match self {
+            Boundary::Torus { shift } => shift.iter().all(|shift| shift % 2 == 0),
+            Boundary::Plane | Boundary::Unbounded => true,
+            _ => false,
+        }
+    }
+
+    /// Parses Golly's notation, e.g. `T30,20+2`, `P`, `K40*,30` or `S50`, returning the grid size
+    /// if one is given. A `*` marks the twisted edges of a Klein bottle: after the width for the
+    /// top and bottom edges, after the height for the sides. A `+n` or `-n` after the width shifts
+    /// the top and bottom edges of a torus, after the height the sides.
+    pub fn parse(spec: &str) -> Result<(Boundary, Option<[u32; 2]>)> {
+        let spec = spec.trim().trim_start_matches(':');
+        let mut chars = spec.chars();
+        let kind = chars.next().context("empty boundary")?.to_ascii_uppercase();
+        let dimensions = chars.as_str();
+
+        let mut size = [None; 2];
+        let mut twisted = [false; 2];
+        let mut shift = [0; 2];
+        if !dimensions.is_empty() {
+            let mut parts = dimensions.split(',');
+            let width = parts.next().unwrap_or_default();
+            let height = match (kind, parts.next()) {
+                (_, Some(height)) => height,
+                // A sphere is square, so `S50` gives both.
+                ('S', None) => width,
+                _ => bail!("invalid boundary {spec:?}: expected a width and a height"),
+            };
+            ensure!(
+                parts.next().is_none(),
+                "invalid boundary {spec:?}: too many dimensions"
+            );
+            for (axis, dimension) in [width, height].into_iter().enumerate() {
+                let (dimension, shift_value) = match dimension.find(['+', '-']) {
+                    Some(i) => (&dimension[..i], Some(&dimension[i..])),
+                    None => (dimension, None),
+                };
+                let (dimension, star) = match dimension.strip_suffix('*') {
+                    Some(dimension) => (dimension, true),
+                    None => (dimension, false),
+                };
+                twisted[axis] = star;
+                if let Some(shift_value) = shift_value {
+                    shift[axis] = shift_value
+                        .trim_start_matches('+')
+                        .parse()
+                        .with_context(|| format!("invalid shift in boundary {spec:?}"))?;
+                }
+                if !dimension.is_empty() {
+                    let dimension: u32 = dimension
+                        .parse()
+                        .with_context(|| format!("invalid size in boundary {spec:?}"))?;
+                    size[axis] = Some(dimension);
+                }
+            }
+        }
+
+        ensure!(
+            kind == 'T' || shift == [0, 0],
+            "invalid boundary {spec:?}: only a torus can be shifted"
+        );
+        ensure!(
+            kind == 'K' || twisted == [false, false],
+            "invalid boundary {spec:?}: only a Klein bottle has twisted edges"
+        );
+        let boundary = match kind {
+            'T' => {
+                ensure!(
+                    shift[0] == 0 || shift[1] == 0,
+                    "invalid boundary {spec:?}: only one pair of edges can be shifted"
+                );
+                Boundary::Torus { shift }
+            }
+            'P' if size == [Some(0), Some(0)] => return Ok((Boundary::Unbounded, None)),
+            'P' => Boundary::Plane,
+            'K' => {
+                ensure!(
+                    !(twisted[0] && twisted[1]),
+                    "invalid boundary {spec:?}: only one pair of edges can be twisted, use C for both"
+                );
+                Boundary::KleinBottle {
+                    twisted_sides: twisted[1],
+                }
+            }
+            'C' => Boundary::CrossSurface,
+            'S' => Boundary::Sphere,
+            _ => bail!("unknown boundary {spec:?}, expected T, P, K, C or S"),
+        };
+
+        ensure!(
+            !size.contains(&Some(0)),
+            "invalid boundary {spec:?}: the size must be positive"
+        );
+        let size = match size {
+            [Some(width), Some(height)] => Some([width, height]),
+            [None, None] => None,
+            _ => bail!("invalid boundary {spec:?}: give both the width and the height or neither"),
+        };
+        Ok((boundary, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Boundary;
+    use crate::reference::{self, BOUNDARIES};
+
+    #[test]
+    fn parses_golly_notation() {
+        let parse = |spec| Boundary::parse(spec).unwrap();
+        assert_eq!(
+            parse("T30,20+2"),
+            (Boundary::Torus { shift: [0, 2] }, Some([30, 20]))
+        );
+        assert_eq!(
+            parse(":T30-1,20"),
+            (Boundary::Torus { shift: [-1, 0] }, Some([30, 20]))
+        );
+        assert_eq!(parse("T"), (Boundary::default(), None));
+        assert_eq!(parse("P"), (Boundary::Plane, None));
+        assert_eq!(parse("P0,0"), (Boundary::Unbounded, None));
+        assert_eq!(
+            parse("K40*,30"),
+            (
+                Boundary::KleinBottle {
+                    twisted_sides: false
+                },
+                Some([40, 30])
+            )
+        );
+        assert_eq!(
+            parse("K40,30*"),
+            (
+                Boundary::KleinBottle {
+                    twisted_sides: true
+                },
+                Some([40, 30])
+            )
+        );
+        assert_eq!(parse("C12,12"), (Boundary::CrossSurface, Some([12, 12])));
+        assert_eq!(parse("S50"), (Boundary::Sphere, Some([50, 50])));
+
+        for invalid in [
+            "",
+            "X",
+            "T30+1,20+1",
+            "P30+1,20",
+            "K40*,30*",
+            "T30",
+            "T30,20,10",
+            "P0,20",
+            "T0,0",
+        ] {
+            assert!(Boundary::parse(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    /// Runs a few generations of `rule` on the GPU and checks each against the reference.
+    fn check(spec: &str, size: [u32; 2], rule: &str) {
+        reference::check(spec, size, rule, &[]);
+    }
+
+    #[test]
+    fn life_on_non_power_of_two_grids() {
+        for spec in BOUNDARIES {
+            for size in [30, 13] {
+                check(spec, [size, size], "B3/S23");
+            }
+        }
+    }
+
+    #[test]
+    fn larger_than_life_on_non_power_of_two_grids() {
+        for spec in BOUNDARIES {
+            check(spec, [30, 30], "R2,C0,M1,S3..5,B3..4,NM");
+        }
+    }
+
+    #[test]
+    fn rectangular_grids() {
+        for spec in BOUNDARIES.into_iter().filter(|&spec| spec != "S") {
+            for size in [[30, 17], [9, 40]] {
+                check(spec, size, "B3/S23");
+                check(spec, size, "R2,C0,M1,S3..5,B3..4,NM");
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! The unbounded universe. The plane is cut into chunks of `CHUNK_SIZE` by `CHUNK_SIZE` cells, and
+//! only the chunks holding live cells, or within reach of live cells next door, are kept on the
+//! GPU, each in a slot of a pool of chunk-sized stretches of the cell buffers.
+//!
+//! Every generation the kernel reports for each slot whether its chunk has live cells and which
+//! of its neighbours they could spill into next; `Chunks::update` turns that into the chunks to
+//! allocate and the ones to free.
+
+use std::collections::{BTreeSet, HashMap};
+
+use bytemuck::{Pod, Zeroable};
+
+/// Width and height of a chunk in cells. Even, so that chunks line up with lattices that repeat
+/// every two cells, and far wider than the largest neighbourhood, so that a cell only ever looks
+/// into the chunks next to its own.
+pub const CHUNK_SIZE: u32 = 64;
+
+/// The chunk holding a cell.
+pub fn chunk_of([x, y]: [i32; 2]) -> [i32; 2] {
+    let size = CHUNK_SIZE as i32;
+    [x.div_euclid(size), y.div_euclid(size)]
+}
+
+/// Index of the chunk `[dx, dy]` chunks away in `ChunkEntry::neighbours`, and the bit standing for
+/// it in the activity the kernel reports: bit 4, the chunk itself, says that it has live cells,
+/// the others that live cells are close enough to that neighbour to affect it.
+fn neighbour_index([dx, dy]: [i32; 2]) -> usize {
+    ((dy + 1) * 3 + dx + 1) as usize
+}
+
+/// What the kernel knows about the chunk in a slot.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct ChunkEntry {
+    /// The slots of the 3x3 chunks around this one, row by row from the bottom left, or -1 for
+    /// chunks that aren't stored because all their cells are dead. The middle entry is the slot
+    /// itself, or -1 if the slot is free.
+    pub neighbours: [i32; 9],
+    _padding: i32,
+    /// The cell in the bottom left corner of the chunk.
+    pub origin: [i32; 2],
+}
+
+/// Which chunk is stored in which slot.
+#[derive(Default)]
+pub struct Chunks {
+    slots: HashMap<[i32; 2], u32>,
+    /// The chunk in each slot, `None` for free slots.
+    chunks: Vec<Option<[i32; 2]>>,
+}
+
+impl Chunks {
+    /// The chunks overlapping the cells from `min` up to `max` (exclusive) and the ring of chunks
+    /// around them.
+    pub fn covering(min: [i32; 2], max: [i32; 2]) -> Chunks {
+        let mut chunks = Chunks::default();
+        let [x0, y0] = chunk_of(min);
+        let [x1, y1] = chunk_of([max[0] - 1, max[1] - 1]);
+        for y in y0 - 1..=y1 + 1 {
+            for x in x0 - 1..=x1 + 1 {
+                chunks.insert([x, y]);
+            }
+        }
+        chunks
+    }
+
+    /// Number of slots up to the last one in use.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn slot(&self, chunk: [i32; 2]) -> Option<u32> {
+        self.slots.get(&chunk).copied()
+    }
+
+    /// Where a cell lives in the cell buffers, if its chunk is stored.
+    pub fn cell_index(&self, cell: [i32; 2]) -> Option<usize> {
+        let size = CHUNK_SIZE as i32;
+        let chunk = chunk_of(cell);
+        let slot = self.slot(chunk)? as i32;
+        let [x, y] = [cell[0] - chunk[0] * size, cell[1] - chunk[1] * size];
+        Some(((slot * size + y) * size + x) as usize)
+    }
+
+    /// Stores `chunk` in the first free slot and returns the slot.
+    fn insert(&mut self, chunk: [i32; 2]) -> u32 {
+        let slot = match self.chunks.iter().position(Option::is_none) {
+            Some(slot) => {
+                self.chunks[slot] = Some(chunk);
+                slot
+            }
+            None => {
+                self.chunks.push(Some(chunk));
+                self.chunks.len() - 1
+            }
+        } as u32;
+        self.slots.insert(chunk, slot);
+        slot
+    }
+
+    fn remove(&mut self, chunk: [i32; 2]) {
+        if let Some(slot) = self.slots.remove(&chunk) {
+            self.chunks[slot as usize] = None;
+        }
+        while self.chunks.last() == Some(&None) {
+            self.chunks.pop();
+        }
+    }
+
+    /// Keeps the chunks that `activity`, one mask per slot, says are needed for the next
+    /// generation, frees the others and allocates the missing ones, as long as they fit in
+    /// `max_len` slots; the cells of the chunks left out are lost. Returns the newly allocated
+    /// slots, whose cells are left over from earlier chunks and must be cleared, and whether
+    /// every chunk needed fit.
+    pub fn update(&mut self, activity: &[u32], max_len: usize) -> (Vec<u32>, bool) {
+        let mut needed = BTreeSet::new();
+        for (&chunk, &mask) in self.chunks.iter().zip(activity) {
+            let Some([x, y]) = chunk else { continue };
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if mask & 1 << neighbour_index([dx, dy]) != 0 {
+                        needed.insert([x + dx, y + dy]);
+                    }
+                }
+            }
+        }
+
+        let unneeded: Vec<_> = (self.slots.keys())
+            .filter(|chunk| !needed.contains(*chunk))
+            .copied()
+            .collect();
+        for chunk in unneeded {
+            self.remove(chunk);
+        }
+        let missing: Vec<_> = (needed.into_iter())
+            .filter(|chunk| !self.slots.contains_key(chunk))
+            .collect();
+        let mut allocated = Vec::new();
+        for chunk in missing {
+            // Free slots are filled first, so `len()` only grows up to `max_len`.
+            if self.slots.len() >= max_len {
+                return (allocated, false);
+            }
+            allocated.push(self.insert(chunk));
+        }
+        (allocated, true)
+    }
+
+    /// The entry of each slot up to `len()`.
+    pub fn entries(&self) -> Vec<ChunkEntry> {
+        let size = CHUNK_SIZE as i32;
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let Some([x, y]) = *chunk else {
+                    return ChunkEntry {
+                        neighbours: [-1; 9],
+                        ..Zeroable::zeroed()
+                    };
+                };
+                let mut neighbours = [-1; 9];
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if let Some(slot) = self.slot([x + dx, y + dy]) {
+                            neighbours[neighbour_index([dx, dy])] = slot as i32;
+                        }
+                    }
+                }
+                ChunkEntry {
+                    neighbours,
+                    _padding: 0,
+                    origin: [x * size, y * size],
+                }
+            })
+            .collect()
+    }
+
+    /// The slots of the chunks overlapping the cells from `min` up to `max` (exclusive).
+    pub fn in_view(&self, min: [i32; 2], max: [i32; 2]) -> impl Iterator<Item = u32> + '_ {
+        let [x0, y0] = chunk_of(min);
+        let [x1, y1] = chunk_of([max[0] - 1, max[1] - 1]);
+        self.iter()
+            .filter(move |&(_, [x, y])| (x0..=x1).contains(&x) && (y0..=y1).contains(&y))
+            .map(|(slot, _)| slot)
+    }
+
+    /// The slots in use and their chunks.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, [i32; 2])> + '_ {
+        (self.chunks.iter().enumerate()).filter_map(|(slot, chunk)| Some((slot as u32, (*chunk)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{neighbour_index, Chunks, CHUNK_SIZE};
+    use crate::{render::Renderer, simulation::Simulation, Config};
+
+    #[test]
+    fn chunks_follow_activity() {
+        let mut chunks = Chunks::covering([0, 0], [1, 1]);
+        assert_eq!(chunks.len(), 9);
+        let centre = chunks.slot([0, 0]).unwrap() as usize;
+
+        // Live cells in the middle chunk, close to its right edge.
+        let mut activity = vec![0; chunks.len()];
+        activity[centre] = 1 << neighbour_index([0, 0]) | 1 << neighbour_index([1, 0]);
+        let (allocated, complete) = chunks.update(&activity, usize::MAX);
+        assert!(allocated.is_empty() && complete);
+        let mut stored: Vec<_> = chunks.iter().map(|(_, chunk)| chunk).collect();
+        stored.sort();
+        assert_eq!(stored, [[0, 0], [1, 0]]);
+
+        // They move over the edge into the next chunk and reach its top right corner.
+        let right = chunks.slot([1, 0]).unwrap() as usize;
+        let mut activity = vec![0; chunks.len()];
+        activity[right] = 1 << neighbour_index([0, 0]) | 1 << neighbour_index([1, 1]);
+        let (allocated, complete) = chunks.update(&activity, usize::MAX);
+        assert!(complete);
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(chunks.slot([2, 1]), Some(allocated[0]));
+        assert_eq!(chunks.slot([0, 0]), None);
+
+        let entries = chunks.entries();
+        let entry = &entries[right];
+        assert_eq!(entry.origin, [CHUNK_SIZE as i32, 0]);
+        assert_eq!(entry.neighbours[neighbour_index([0, 0])], right as i32);
+        assert_eq!(
+            entry.neighbours[neighbour_index([1, 1])],
+            allocated[0] as i32
+        );
+        assert_eq!(entry.neighbours[neighbour_index([-1, 0])], -1);
+
+        // Spilling into two more chunks with room for only one more: the first is allocated in
+        // the freed slot, and the other left out.
+        let mut activity = vec![0; chunks.len()];
+        activity[right] = 1 << neighbour_index([0, 0]) | 1 << neighbour_index([1, 0]);
+        activity[allocated[0] as usize] =
+            1 << neighbour_index([0, 0]) | 1 << neighbour_index([0, 1]);
+        let (more, complete) = chunks.update(&activity, 3);
+        assert!(!complete);
+        assert_eq!(more.len(), 1);
+        assert_eq!(chunks.iter().count(), 3);
+        let stored = [[1, 0], [2, 1], [2, 0], [2, 2]].map(|chunk| chunks.slot(chunk).is_some());
+        assert_eq!(stored, [true, true, true, false]);
+    }
+
+    #[test]
+    fn gliders_travel_across_chunks() {
+        let args = ["--boundary", "P0,0", "--grid-size", "40"];
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        let Some(mut renderer) = Renderer::headless(&config) else {
+            return;
+        };
+        // A glider heading down and to the right, a cell every four generations.
+        let glider = [[1, 2], [2, 1], [0, 0], [1, 0], [2, 0]];
+        let mut cells = vec![0; 40 * 40];
+        for [x, y] in glider {
+            cells[(y + 30) * 40 + x + 30] = 1;
+        }
+        renderer.set_cells(&cells);
+
+        for _ in 0..256 {
+            renderer.step(1);
+        }
+        let mut expected: Vec<[i32; 2]> = glider
+            .iter()
+            .map(|&[x, y]| [x as i32 + 30 + 64, y as i32 + 30 - 64])
+            .collect();
+        expected.sort();
+        assert_eq!(renderer.live_cells(), expected);
+        // Far from the edges of its chunk, the glider needs no other.
+        assert_eq!(renderer.chunk_count(), 1);
+    }
+}
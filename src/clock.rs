@@ -0,0 +1,140 @@
+//! The simulation clock, which runs the universe at a set number of generations per second
+//! whatever the frame rate: slow speeds wait between generations, fast ones run many generations
+//! per frame.
+
+use std::time::{Duration, Instant};
+
+/// Slowest and fastest speeds, in generations per second.
+pub const SPEEDS: std::ops::RangeInclusive<f64> = 0.1..=1e9;
+
+/// Longest a frame should spend running generations, judging by how fast the engine ran them
+/// before. An engine that can't keep up falls behind by the rest instead of spending ever longer
+/// frames catching up.
+const MAX_BACKLOG: Duration = Duration::from_millis(250);
+
+pub struct Clock {
+    /// Target generations per second.
+    speed: f64,
+    /// Generations are due in multiples of this many, the engine's step.
+    step: u64,
+    /// When the clock last started counting.
+    start: Instant,
+    /// Generations handed out since `start`.
+    done: u64,
+    /// Generations per second the engine last managed, if known yet.
+    rate: Option<f64>,
+}
+
+impl Clock {
+    pub fn new(speed: f64, step: u64, now: Instant) -> Clock {
+        Clock {
+            speed,
+            step,
+            start: now,
+            done: 0,
+            rate: None,
+        }
+    }
+
+    /// The generations to run now, a multiple of the step. Until the engine's speed is known, a
+    /// single step.
+    pub fn due(&mut self, now: Instant) -> u64 {
+        let elapsed = now.saturating_duration_since(self.start).as_secs_f64();
+        let total = (elapsed * self.speed) as u64;
+        let mut due = total.saturating_sub(self.done) / self.step * self.step;
+        let rate = self.rate.unwrap_or(0.).min(self.speed);
+        let most =
+            ((MAX_BACKLOG.as_secs_f64() * rate) as u64 / self.step * self.step).max(self.step);
+        if due > most {
+            // Drop the backlog: start counting again from now.
+            due = most;
+            self.start = now;
+            self.done = 0;
+        } else {
+            self.done += due;
+        }
+        due
+    }
+
+    /// Records that running `generations` took `took`, drawing them included, to size the
+    /// frames to come.
+    pub fn ran(&mut self, generations: u64, took: Duration) {
+        if generations > 0 {
+            self.rate = Some(generations as f64 / took.as_secs_f64());
+        }
+    }
+
+    /// When the next step is due.
+    pub fn next_due(&self) -> Instant {
+        let generations = (self.done + self.step) as f64;
+        self.start + Duration::from_secs_f64(generations / self.speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Clock;
+
+    #[test]
+    fn generations_follow_the_speed() {
+        let start = Instant::now();
+        let at = |seconds: f64| start + Duration::from_secs_f64(seconds);
+
+        let mut clock = Clock::new(0.5, 1, start);
+        assert_eq!(clock.due(at(1.9)), 0);
+        assert_eq!(clock.next_due(), at(2.0));
+        assert_eq!(clock.due(at(2.1)), 1);
+        assert_eq!(clock.due(at(2.2)), 0);
+        assert_eq!(clock.next_due(), at(4.0));
+
+        // A million generations per second at 60 frames per second, on an engine that keeps up.
+        let mut clock = Clock::new(1e6, 1, start);
+        clock.ran(1, Duration::from_micros(1));
+        let frames: Vec<_> = (1..=60)
+            .map(|frame| clock.due(at(frame as f64 / 60.)))
+            .collect();
+        assert_eq!(frames.iter().sum::<u64>(), 1_000_000);
+        assert!(frames.iter().all(|&due| (16_666..=16_667).contains(&due)));
+
+        // Steps of a thousand generations are handed out whole.
+        let mut clock = Clock::new(1500., 1000, start);
+        assert_eq!(clock.due(at(0.5)), 0);
+        assert_eq!(clock.due(at(1.)), 1000);
+        assert_eq!(clock.due(at(1.7)), 1000);
+        assert_eq!(clock.next_due(), at(2.));
+    }
+
+    #[test]
+    fn slow_frames_drop_the_backlog() {
+        let start = Instant::now();
+        let at = |seconds: f64| start + Duration::from_secs_f64(seconds);
+        let mut clock = Clock::new(1000., 1, start);
+        clock.ran(1, Duration::from_micros(10));
+        assert_eq!(clock.due(at(0.125)), 125);
+        // A frame that took five seconds only gets a quarter of a second's worth...
+        assert_eq!(clock.due(at(5.125)), 250);
+        // ...and the clock carries on from there.
+        assert_eq!(clock.due(at(5.25)), 125);
+    }
+
+    #[test]
+    fn frames_wait_for_slow_engines() {
+        let start = Instant::now();
+        let at = |seconds: f64| start + Duration::from_secs_f64(seconds);
+        let mut clock = Clock::new(1e6, 2, start);
+        // The engine's speed is unknown at first...
+        assert_eq!(clock.due(at(0.1)), 2);
+        // ...and then it only manages a thousand generations per second: a frame gets a quarter
+        // of a second of that, not of the target speed.
+        clock.ran(2, Duration::from_millis(2));
+        assert_eq!(clock.due(at(0.2)), 250);
+        // Slowing down to half that halves the frames too, rounded down to whole steps.
+        clock.ran(250, Duration::from_millis(500));
+        assert_eq!(clock.due(at(0.7)), 124);
+        // An engine faster than the target still runs at the target speed.
+        clock.ran(124, Duration::from_micros(1));
+        assert_eq!(clock.due(at(0.8)), 100_000);
+    }
+}
@@ -0,0 +1,533 @@
+//! The CPU engine: the compute shader's rules and boundaries in plain Rust, each generation split
+//! into bands of rows computed on all cores. It runs without a GPU, and the tests hold the shader
+//! to it.
+
+use std::thread;
+
+use crate::{
+    boundary::Boundary,
+    rule::{Neighbourhood, Rule},
+    simulation::Simulation,
+    stencil::Stencil,
+    topology::Topology,
+    Config,
+};
+
+/// The fewest cells worth a thread of their own each generation.
+const MIN_BAND_CELLS: usize = 1 << 14;
+
+/// A bounded grid of cells, evaluated the way `compute.wgsl` evaluates it.
+pub struct Cpu {
+    size: [u32; 2],
+    boundary: Boundary,
+    topology: Topology,
+    /// Replaces the rule's neighbourhood when set.
+    stencil: Option<Stencil>,
+    rule: Rule,
+    /// For isotropic rules, bit 0 of entry `c`: a dead cell with configuration `c` is born, bit 1:
+    /// a live one survives.
+    transitions: [u32; 256],
+    cells: Vec<u32>,
+    next: Vec<u32>,
+    generation: u64,
+}
+
+impl Cpu {
+    /// The grid of `config` holding `cells`, row by row from the bottom.
+    pub fn new(config: &Config, cells: Vec<u32>) -> Cpu {
+        assert!(
+            config.boundary != Boundary::Unbounded,
+            "the CPU engine runs bounded grids"
+        );
+        let mut cpu = Cpu {
+            size: config.grid_size,
+            boundary: config.boundary,
+            topology: config.topology,
+            stencil: config.stencil.clone(),
+            rule: config.rule.clone(),
+            transitions: [0; 256],
+            next: vec![0; cells.len()],
+            cells,
+            generation: 0,
+        };
+        cpu.set_rule(&config.rule);
+        cpu
+    }
+
+    /// The index of the cell a position stands for under the boundary conditions, or `None` if
+    /// it is dead.
+    fn index(&self, [x, y]: [i32; 2]) -> Option<usize> {
+        let [width, height] = self.size.map(|size| size as i32);
+        let mut p = [x, y];
+        match self.boundary {
+            Boundary::Torus { shift } => {
+                if shift[0] != 0 {
+                    p[0] += y.div_euclid(height) * shift[0];
+                } else {
+                    p[1] += x.div_euclid(width) * shift[1];
+                }
+                p = [p[0].rem_euclid(width), p[1].rem_euclid(height)];
+            }
+            Boundary::Plane | Boundary::Unbounded => {}
+            Boundary::KleinBottle { .. } | Boundary::CrossSurface => {
+                // Each crossing of a twisted pair of edges mirrors the position along them.
+                let crossings = [x.div_euclid(width), y.div_euclid(height)];
+                p = [x.rem_euclid(width), y.rem_euclid(height)];
+                let (twisted_ends, twisted_sides) = match self.boundary {
+                    Boundary::KleinBottle { twisted_sides } => (!twisted_sides, twisted_sides),
+                    _ => (true, true),
+                };
+                if twisted_ends && crossings[1] % 2 != 0 {
+                    p[0] = width - 1 - p[0];
+                }
+                if twisted_sides && crossings[0] % 2 != 0 {
+                    p[1] = height - 1 - p[1];
+                }
+            }
+            Boundary::Sphere => {
+                // Crossing the top edge leads in through the left edge and crossing the bottom
+                // edge in through the right one. Positions beyond a corner cross two edges.
+                let n = width;
+                for _ in 0..2 {
+                    let [x, y] = p;
+                    p = if y >= n {
+                        [y - n, n - 1 - x]
+                    } else if x < 0 {
+                        [n - 1 - y, n + x]
+                    } else if y < 0 {
+                        [n + y, n - 1 - x]
+                    } else if x >= n {
+                        [n - 1 - y, x - n]
+                    } else {
+                        p
+                    };
+                }
+            }
+        }
+        let [x, y] = p;
+        ((0..width).contains(&x) && (0..height).contains(&y)).then_some((y * width + x) as usize)
+    }
+
+    fn state(&self, position: [i32; 2]) -> u32 {
+        self.index(position).map_or(0, |i| self.cells[i])
+    }
+
+    /// Whether a cell counts as a live neighbour; refractory cells don't.
+    fn active(&self, position: [i32; 2]) -> u32 {
+        u32::from(self.state(position) == 1)
+    }
+
+    /// The next state of the cell at `[x, y]`.
+    fn evolve(&self, [x, y]: [i32; 2]) -> u32 {
+        let state = self.state([x, y]);
+        if let Some(tree) = &self.rule.tree {
+            // NW, NE, SW, SE, N, W, E, S (or only N, W, E, S), then the centre.
+            const OFFSETS: [[i32; 2]; 9] = [
+                [-1, 1],
+                [1, 1],
+                [-1, -1],
+                [1, -1],
+                [0, 1],
+                [-1, 0],
+                [1, 0],
+                [0, -1],
+                [0, 0],
+            ];
+            let offsets = &OFFSETS[OFFSETS.len() - 1 - tree.neighbours as usize..];
+            let mut cells = [0; 9];
+            for (cell, [dx, dy]) in cells.iter_mut().zip(offsets) {
+                *cell = self.state([x + dx, y + dy]);
+            }
+            return tree.next(&cells[..offsets.len()]);
+        }
+
+        let active = |dx: i32, dy: i32| self.active([x + dx, y + dy]);
+        let mut count = match (self.topology, &self.stencil) {
+            (Topology::TriangularVertex | Topology::TriangularEdge, _) => {
+                self.triangle_count([x, y])
+            }
+            (Topology::Hexagonal, _) => self.hex_count([x, y]),
+            (Topology::Square, None) if self.rule.neighbourhood == Neighbourhood::Hexagonal => {
+                self.hex_count([x, y])
+            }
+            (Topology::Square, Some(stencil)) => {
+                let count: i32 = (stencil.entries.iter())
+                    .map(|&[dx, dy, weight]| weight * active(dx, dy) as i32)
+                    .sum();
+                // Negative sums match no count.
+                u32::try_from(count).unwrap_or(u32::MAX)
+            }
+            (Topology::Square, None) => {
+                let r = self.rule.radius as i32;
+                let mut count = 0;
+                for dy in -r..=r {
+                    let half = match self.rule.neighbourhood {
+                        Neighbourhood::VonNeumann => r - dy.abs(),
+                        _ => r,
+                    };
+                    for dx in -half..=half {
+                        if (dx, dy) != (0, 0) {
+                            count += active(dx, dy);
+                        }
+                    }
+                }
+                count
+            }
+        };
+        if self.rule.include_centre {
+            // Wraps like the shader's u32 for stencils summing to no count.
+            count = count.wrapping_add(u32::from(state == 1));
+        }
+
+        let (born, survives) = match self.rule.isotropic {
+            Some(_) => {
+                // The live Moore neighbours as a bitmask, starting at N and going clockwise.
+                let offsets = [
+                    [0, 1],
+                    [1, 1],
+                    [1, 0],
+                    [1, -1],
+                    [0, -1],
+                    [-1, -1],
+                    [-1, 0],
+                    [-1, 1],
+                ];
+                let configuration = (offsets.iter().enumerate())
+                    .map(|(bit, &[dx, dy])| active(dx, dy) << bit)
+                    .sum::<u32>();
+                let transition = self.transitions[configuration as usize];
+                (transition & 1 != 0, transition & 2 != 0)
+            }
+            None => (
+                self.rule.birth.contains(count),
+                self.rule.survival.contains(count),
+            ),
+        };
+        match state {
+            0 => born.into(),
+            1 if survives => 1,
+            // Generations rules send live cells that don't survive into the refractory states.
+            1 if self.rule.states > 2 => 2,
+            1 => 0,
+            _ if state + 1 >= self.rule.states => 0,
+            _ => state + 1,
+        }
+    }
+
+    /// The six neighbours of a hexagonal cell. On the hexagonal lattice the diagonal neighbours
+    /// depend on whether the row is shifted; on the square grid the lattice is emulated by skewing
+    /// it.
+    fn hex_count(&self, [x, y]: [i32; 2]) -> u32 {
+        let active = |x: i32, y: i32| self.active([x, y]);
+        let hexagonal = self.topology == Topology::Hexagonal;
+        // Diagonal neighbours in the rows above and below: left and left + 1.
+        let left = if hexagonal && y.rem_euclid(2) == 1 {
+            x
+        } else {
+            x - 1
+        };
+        let up = active(left, y + 1) + active(left + 1, y + 1);
+        let side = active(x - 1, y) + active(x + 1, y);
+        if hexagonal {
+            return up + side + active(left, y - 1) + active(left + 1, y - 1);
+        }
+        // Golly's emulation: NW, N, W, E, S, SE.
+        up + side + active(x, y - 1) + active(x + 1, y - 1)
+    }
+
+    /// The neighbours of a triangle. A triangle pointing up shares its base with the one below
+    /// it, so its corners touch five cells in the row below and three above; pointing down it is
+    /// the reverse.
+    fn triangle_count(&self, [x, y]: [i32; 2]) -> u32 {
+        let active = |x: i32, y: i32| self.active([x, y]);
+        let side = active(x - 1, y) + active(x + 1, y);
+        let up = (x + y).rem_euclid(2) == 0;
+        let (base, apex) = if up { (y - 1, y + 1) } else { (y + 1, y - 1) };
+        if self.topology == Topology::TriangularEdge {
+            return side + active(x, base);
+        }
+        let mut count = side + active(x - 2, y) + active(x + 2, y);
+        count += (-2..=2).map(|dx| active(x + dx, base)).sum::<u32>();
+        count += (-1..=1).map(|dx| active(x + dx, apex)).sum::<u32>();
+        count
+    }
+}
+
+impl Simulation for Cpu {
+    fn set_rule(&mut self, rule: &Rule) {
+        self.rule = rule.clone();
+        if let Some(isotropic) = &rule.isotropic {
+            self.transitions = isotropic.transitions();
+        }
+    }
+
+    fn step(&mut self, generations: u64) {
+        let width = self.size[0] as usize;
+        // Small grids take less time to compute than to hand out to threads.
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let threads = threads
+            .min(self.cells.len().div_ceil(MIN_BAND_CELLS))
+            .max(1);
+        let rows_per_band = (self.size[1] as usize).div_ceil(threads).max(1);
+        for _ in 0..generations {
+            let mut next = std::mem::take(&mut self.next);
+            let this = &*self;
+            let evolve_band = move |band: usize, rows: &mut [u32]| {
+                for (i, cell) in rows.iter_mut().enumerate() {
+                    let i = band * rows_per_band * width + i;
+                    *cell = this.evolve([(i % width) as i32, (i / width) as i32]);
+                }
+            };
+            if threads == 1 {
+                evolve_band(0, &mut next);
+            } else {
+                thread::scope(|scope| {
+                    for (band, rows) in next.chunks_mut(rows_per_band * width).enumerate() {
+                        scope.spawn(move || evolve_band(band, rows));
+                    }
+                });
+            }
+            self.next = std::mem::replace(&mut self.cells, next);
+            self.generation += 1;
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn population(&self) -> u64 {
+        self.cells.iter().filter(|&&state| state == 1).count() as u64
+    }
+
+    /// Cells outside the grid read as dead.
+    fn region(&self, origin: [i64; 2], [width, height]: [u32; 2]) -> Vec<u32> {
+        let [grid_width, grid_height] = self.size.map(i64::from);
+        (0..i64::from(height))
+            .flat_map(|y| (0..i64::from(width)).map(move |x| [origin[0] + x, origin[1] + y]))
+            .map(|[x, y]| {
+                let inside = (0..grid_width).contains(&x) && (0..grid_height).contains(&y);
+                match inside {
+                    true => self.cells[(y * grid_width + x) as usize],
+                    false => 0,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::Cpu;
+    use crate::{render::Renderer, simulation::Simulation, Config};
+
+    fn config(args: &[&str]) -> Config {
+        Config::from_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn gliders_cross_the_torus() {
+        let config = config(&["--grid-size", "16x12"]);
+        let mut cells = vec![0; 16 * 12];
+        for [x, y] in [[1, 0], [2, 1], [0, 2], [1, 2], [2, 2]] {
+            cells[y * 16 + x] = 1;
+        }
+        let mut cpu = Cpu::new(&config, cells.clone());
+        // A glider moves a cell down and to the right every four generations, so after 192 it
+        // has gone round the torus 12 times one way and 16 times the other.
+        cpu.step(192);
+        assert_eq!(cpu.generation(), 192);
+        assert_eq!(cpu.population(), 5);
+        assert_eq!(cpu.region([0, 0], [16, 12]), cells);
+    }
+
+    /// Runs `generations` generations of `cells` on the GPU and on the CPU, and panics at the
+    /// first cell of the first generation where they differ.
+    fn compare(
+        renderer: &mut Renderer,
+        config: &Config,
+        cells: Vec<u32>,
+        generations: u64,
+        what: &str,
+    ) {
+        let [width, _] = config.grid_size;
+        renderer.set_cells(&cells);
+        let mut cpu = Cpu::new(config, cells);
+        for generation in 1..=generations {
+            renderer.step(1);
+            cpu.step(1);
+            let expected = cpu.region([0, 0], config.grid_size);
+            let cells = renderer.cells();
+            if let Some(i) = (0..cells.len()).find(|&i| cells[i] != expected[i]) {
+                let [x, y] = [i as u32 % width, i as u32 / width];
+                panic!(
+                    "generation {generation} of {what}: cell ({x}, {y}) is {} on the GPU, {} on the CPU",
+                    cells[i], expected[i]
+                );
+            }
+        }
+        assert_eq!(renderer.population(), cpu.population(), "{what}");
+    }
+
+    /// Runs a soup filling the grid on the GPU and on the CPU.
+    fn check(args: &[&str]) {
+        let config = config(args);
+        let Some(mut renderer) = Renderer::fallback(&config) else {
+            return;
+        };
+        let [width, height] = config.grid_size;
+        let mut rng = StdRng::seed_from_u64(u64::from(width * height));
+        let states = config.rule.states;
+        let cells: Vec<u32> = (0..width * height)
+            .map(|_| match rng.gen_bool(0.5) {
+                true => rng.gen_range(1..states),
+                false => 0,
+            })
+            .collect();
+        compare(&mut renderer, &config, cells, 8, &format!("{args:?}"));
+    }
+
+    /// Each way the GPU can store and compute the cells of a Life-like rule.
+    const VARIANTS: [&[&str]; 5] = [
+        &[],
+        &["--storage", "packed"],
+        &["--storage", "texture"],
+        &["--kernel", "tiled"],
+        &["--active-tiles"],
+    ];
+
+    #[test]
+    fn random_soups() {
+        // 8x8 soups, 256 to a 192x192 grid, each in a 12x12 square of its own at first: Life on
+        // each variant on a plane and on a torus, then an isotropic rule and a Generations rule on
+        // each variant that runs them, taking turns at the boundaries. 4608 soups in all.
+        let rules = [("B3/S23", 2), ("B3/S2-i34q", 1), ("345/2/4", 1)];
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut soups = 0;
+        for (rule, boundaries) in rules {
+            for variant in VARIANTS {
+                for _ in 0..boundaries {
+                    let boundary = ["P", "T"][soups / 256 % 2];
+                    let mut args = vec!["--grid-size", "192", "--boundary", boundary];
+                    args.extend(["--rule", rule].iter().chain(variant));
+                    // Packed cells only run two-state Life-like rules.
+                    let Ok(config) = Config::from_args(args.iter().map(|arg| arg.to_string()))
+                    else {
+                        assert_eq!(variant, ["--storage", "packed"], "{args:?}");
+                        continue;
+                    };
+                    let Some(mut renderer) = Renderer::fallback(&config) else {
+                        return;
+                    };
+                    let mut cells = vec![0; 192 * 192];
+                    for soup in 0..256 {
+                        let corner = [soup % 16 * 12 + 2, soup / 16 * 12 + 2];
+                        for y in 0..8 {
+                            for x in 0..8 {
+                                cells[(corner[1] + y) * 192 + corner[0] + x] =
+                                    u32::from(rng.gen_bool(0.5));
+                            }
+                        }
+                    }
+                    let what = format!(
+                        "soups {:?} of {rule} on {boundary} {variant:?}",
+                        soups..soups + 256
+                    );
+                    compare(&mut renderer, &config, cells, 32, &what);
+                    soups += 256;
+                }
+            }
+        }
+        assert_eq!(soups, 4608);
+    }
+
+    /// Draws a pattern given as plaintext rows, top row first, with its bottom left corner at
+    /// `[x, y]`.
+    fn place(cells: &mut [u32], width: usize, [x, y]: [usize; 2], rows: &[&str]) {
+        for (row, line) in rows.iter().rev().enumerate() {
+            for (column, cell) in line.chars().enumerate() {
+                cells[(y + row) * width + x + column] = u32::from(cell == 'O');
+            }
+        }
+    }
+
+    #[test]
+    fn known_patterns() {
+        let gun = [
+            "........................O...........",
+            "......................O.O...........",
+            "............OO......OO............OO",
+            "...........O...O....OO............OO",
+            "OO........O.....O...OO..............",
+            "OO........O...O.OO....O.O...........",
+            "..........O.....O.......O...........",
+            "...........O...O....................",
+            "............OO......................",
+        ];
+        // Gliders heading across the edges and the gun's gliders hitting them, on a plane and on
+        // a torus.
+        let patterns: [(&str, &[&str], [usize; 2], u64); 4] = [
+            ("blinker", &["OOO"], [30, 20], 4),
+            ("glider", &[".O.", "..O", "OOO"], [2, 4], 64),
+            ("R-pentomino", &[".OO", "OO.", ".O."], [30, 18], 100),
+            ("Gosper gun", &gun, [4, 24], 120),
+        ];
+        for variant in VARIANTS {
+            for boundary in ["P", "T"] {
+                let mut args = vec!["--grid-size", "64x40", "--boundary", boundary];
+                args.extend(variant);
+                let config = config(&args);
+                let Some(mut renderer) = Renderer::fallback(&config) else {
+                    return;
+                };
+                for (name, rows, corner, generations) in patterns {
+                    let mut cells = vec![0; 64 * 40];
+                    place(&mut cells, 64, corner, rows);
+                    let what = format!("the {name} on {boundary} {variant:?}");
+                    compare(&mut renderer, &config, cells, generations, &what);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_compute_shader() {
+        for spec in ["T", "T+3,", "T,-2", "P", "K*,", "K,*", "C", "S"] {
+            let size = if spec == "S" { "30" } else { "30x17" };
+            check(&["--boundary", spec, "--grid-size", size]);
+        }
+        let grid = ["--grid-size", "30x18"];
+        for (rule, extra) in [
+            ("/2/3", &["--boundary", "K*,"][..]),
+            ("345/2/4", &["--boundary", "C"]),
+            ("B2-a/S12", &["--boundary", "P"]),
+            ("B3/S2-i34q", &["--boundary", "T+3,"]),
+            (
+                "R2,C0,M1,S3..5,B3..4,NN",
+                &["--boundary", "S", "--grid-size", "30"],
+            ),
+            ("R3,C0,M1,S8..14,B9..12,NM", &[]),
+            ("B2/S23", &["--stencil", "knight"]),
+            ("B4/S46", &["--stencil", "1,2,1;2,0,2;1,-1,1"]),
+            ("B2/S34H", &[]),
+            ("B2/S34H", &["--topology", "square", "--boundary", "K,*"]),
+            ("B2/S34", &["--topology", "hex", "--boundary", "P"]),
+            ("R1,C0,M0,S2..3,B4..5,NM", &["--topology", "triangular"]),
+            (
+                "B2/S12",
+                &["--topology", "triangular-edge", "--boundary", "P"],
+            ),
+            ("WireWorld", &["--boundary", "S", "--grid-size", "30"]),
+        ] {
+            let args: Vec<_> = ["--rule", rule]
+                .iter()
+                .chain(&grid)
+                .chain(extra)
+                .copied()
+                .collect();
+            check(&args);
+        }
+    }
+}
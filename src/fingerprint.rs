@@ -0,0 +1,320 @@
+//! Fingerprints of whole generations, and what a run of them says about the universe: that it
+//! died out, stopped changing, repeats, or repeats further along.
+//!
+//! A fingerprint is the bottom left corner of the bounding box of the cells that aren't dead,
+//! their number, and two sums of hashes of their states and positions from that corner. The sums
+//! don't depend on the order the cells are visited in, so the GPU can gather them with atomics,
+//! and they stay the same when the whole pattern moves, so that a generation that repeats an
+//! earlier one somewhere else shows up as well as one that repeats it in place.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+/// Generations remembered, the longest period found.
+const HISTORY: usize = 4096;
+
+/// Words of a fingerprint as the compute shader gathers it.
+pub const FINGERPRINT_WORDS: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// Bottom left corner of the bounding box of the cells that aren't dead.
+    pub min: [i32; 2],
+    /// Number of cells that aren't dead.
+    pub cells: u32,
+    pub hashes: [u32; 2],
+}
+
+impl Fingerprint {
+    /// Reads a fingerprint from the words the compute shader gathers: the corner's coordinates
+    /// with their sign bit flipped and then inverted, the number of cells and the hashes.
+    pub fn from_words(words: [u32; FINGERPRINT_WORDS]) -> Fingerprint {
+        let coordinate = |word: u32| (!word ^ 0x8000_0000) as i32;
+        Fingerprint {
+            min: [coordinate(words[0]), coordinate(words[1])],
+            cells: words[2],
+            hashes: [words[3], words[4]],
+        }
+    }
+}
+
+#[cfg(test)]
+impl Fingerprint {
+    /// The fingerprint the compute shader gathers from cells that aren't dead, given by position
+    /// and state.
+    pub fn of(cells: &[([i32; 2], u32)]) -> Fingerprint {
+        let min = [0, 1].map(|axis| {
+            (cells.iter())
+                .map(|(position, _)| position[axis])
+                .min()
+                .unwrap_or(i32::MAX)
+        });
+        let mut hashes = [0u32; 2];
+        for &([x, y], state) in cells {
+            let key = hash32(((x - min[0]) as u32) ^ hash32(((y - min[1]) as u32) ^ hash32(state)));
+            hashes[0] = hashes[0].wrapping_add(key);
+            hashes[1] = hashes[1].wrapping_add(hash32(key ^ 0x9e37_79b9));
+        }
+        Fingerprint {
+            min,
+            cells: cells.len() as u32,
+            hashes,
+        }
+    }
+}
+
+/// lowbias32, a 32-bit integer hash, as in `compute.wgsl`.
+#[cfg(test)]
+fn hash32(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^ (x >> 16)
+}
+
+/// What the universe is doing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Every cell is dead.
+    Extinct,
+    /// Nothing changes any more.
+    Still,
+    /// The universe comes back to the same generation every `period` generations.
+    Oscillating { period: u64 },
+    /// The universe comes back to the same generation moved by `displacement` every `period`
+    /// generations.
+    Translating { period: u64, displacement: [i32; 2] },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Extinct => write!(f, "extinct"),
+            Event::Still => write!(f, "still"),
+            Event::Oscillating { period } => write!(f, "period {period} oscillation"),
+            Event::Translating {
+                period,
+                displacement: [dx, dy],
+            } => write!(f, "translating by ({dx},{dy}) every {period} generations"),
+        }
+    }
+}
+
+/// Watches the fingerprints of consecutive generations, keeping a ring of the recent ones.
+#[derive(Default)]
+pub struct Detector {
+    /// The generations remembered and their fingerprints, oldest first.
+    history: VecDeque<(u64, Fingerprint)>,
+    /// The latest generation remembered with each number of cells and hashes, and where its
+    /// cells started.
+    latest: HashMap<(u32, [u32; 2]), (u64, [i32; 2])>,
+    /// What the universe has been doing since the last event, if anything the detector knows.
+    state: Option<Event>,
+}
+
+impl Detector {
+    /// Records the fingerprint of the next generation, and returns what the universe does if it
+    /// has just started doing it.
+    pub fn push(&mut self, generation: u64, fingerprint: Fingerprint) -> Option<Event> {
+        let key = (fingerprint.cells, fingerprint.hashes);
+        let earlier = (self.latest.get(&key)).map(|&(earlier, min)| (generation - earlier, min));
+        let state = match earlier {
+            _ if fingerprint.cells == 0 => Some(Event::Extinct),
+            None => None,
+            Some((1, min)) if min == fingerprint.min => Some(Event::Still),
+            Some((period, min)) if min == fingerprint.min => Some(Event::Oscillating { period }),
+            Some((period, min)) => Some(Event::Translating {
+                period,
+                displacement: [0, 1].map(|axis| fingerprint.min[axis] - min[axis]),
+            }),
+        };
+
+        if self.history.len() == HISTORY {
+            let (oldest, fingerprint) = self.history.pop_front().unwrap();
+            let key = (fingerprint.cells, fingerprint.hashes);
+            if self.latest.get(&key).map(|&(generation, _)| generation) == Some(oldest) {
+                self.latest.remove(&key);
+            }
+        }
+        self.history.push_back((generation, fingerprint));
+        self.latest.insert(key, (generation, fingerprint.min));
+
+        let changed = state != self.state;
+        self.state = state;
+        state.filter(|_| changed)
+    }
+
+    /// What the universe has been doing since the last event.
+    pub fn state(&self) -> Option<Event> {
+        self.state
+    }
+
+    /// The last `n` generations remembered and their fingerprints, oldest first.
+    pub fn recent(&self, n: usize) -> impl Iterator<Item = &(u64, Fingerprint)> {
+        self.history.range(self.history.len().saturating_sub(n)..)
+    }
+}
+
+#[cfg(test)]
+impl Detector {
+    /// The last generation recorded and its fingerprint.
+    pub fn last(&self) -> Option<(u64, Fingerprint)> {
+        self.history.back().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{Detector, Event, Fingerprint};
+    use crate::{cpu::Cpu, render::Renderer, rule::Rule, simulation::Simulation, Config};
+
+    fn config(args: &[&str]) -> Config {
+        Config::from_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    /// The cells of a `width` wide grid that aren't dead.
+    fn cells(grid: &[u32], width: u32) -> Vec<([i32; 2], u32)> {
+        (grid.iter().enumerate())
+            .filter(|&(_, &state)| state != 0)
+            .map(|(i, &state)| {
+                let [x, y] = [i as u32 % width, i as u32 / width];
+                ([x as i32, y as i32], state)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detectors_tell_what_patterns_do() {
+        let config = config(&["--grid-size", "32", "--boundary", "P"]);
+        for (pattern, events) in [
+            (&[[5, 5]][..], vec![(1, Event::Extinct)]),
+            (&[[5, 5], [6, 5], [5, 6], [6, 6]], vec![(2, Event::Still)]),
+            // Becomes a block.
+            (&[[5, 5], [6, 5], [5, 6]], vec![(2, Event::Still)]),
+            (
+                &[[5, 5], [6, 5], [7, 5]],
+                vec![(3, Event::Oscillating { period: 2 })],
+            ),
+            (
+                &[[5, 20], [6, 19], [4, 18], [5, 18], [6, 18]],
+                vec![(
+                    5,
+                    Event::Translating {
+                        period: 4,
+                        displacement: [1, -1],
+                    },
+                )],
+            ),
+        ] {
+            let mut grid = vec![0; 32 * 32];
+            for &[x, y] in pattern {
+                grid[y * 32 + x] = 1;
+            }
+            let mut cpu = Cpu::new(&config, grid);
+            let mut detector = Detector::default();
+            let mut seen = Vec::new();
+            for generation in 1..=60 {
+                cpu.step(1);
+                let fingerprint = Fingerprint::of(&cells(&cpu.region([0, 0], [32, 32]), 32));
+                seen.extend(
+                    detector
+                        .push(generation, fingerprint)
+                        .map(|e| (generation, e)),
+                );
+            }
+            assert_eq!(seen, events, "{pattern:?}");
+        }
+    }
+
+    #[test]
+    fn the_gpu_fingerprints_every_generation() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for variant in [
+            &["--grid-size", "64"][..],
+            &["--grid-size", "64", "--storage", "packed"],
+            &[
+                "--grid-size",
+                "64",
+                "--storage",
+                "texture",
+                "--boundary",
+                "P",
+            ],
+            &["--grid-size", "64", "--active-tiles", "--rule", "B2/S/C3"],
+            &["--grid-size", "48", "--boundary", "P0,0"],
+        ] {
+            let mut args = variant.to_vec();
+            args.push("--detect");
+            let config = config(&args);
+            let Some(mut renderer) = Renderer::fallback(&config) else {
+                return;
+            };
+            let [width, height] = config.grid_size;
+            let mut grid = vec![0; (width * height) as usize];
+            for y in 16..32 {
+                for x in 16..32 {
+                    grid[y * width as usize + x] = rng.gen_range(0..config.rule.states);
+                }
+            }
+            renderer.set_cells(&grid);
+            for generation in 1..=12 {
+                renderer.step(1);
+                let cells = match variant.contains(&"P0,0") {
+                    true => (renderer.live_cells().into_iter())
+                        .map(|position| (position, 1))
+                        .collect(),
+                    false => cells(&renderer.cells(), width),
+                };
+                let last = renderer.detector().and_then(|detector| detector.last());
+                assert_eq!(
+                    last,
+                    Some((generation, Fingerprint::of(&cells))),
+                    "{variant:?}"
+                );
+            }
+        }
+
+        // A glider alone in an unbounded universe.
+        let config = config(&["--grid-size", "16", "--boundary", "P0,0", "--detect"]);
+        let Some(mut renderer) = Renderer::fallback(&config) else {
+            return;
+        };
+        let mut grid = vec![0; 16 * 16];
+        for [x, y] in [[1, 2], [2, 1], [0, 0], [1, 0], [2, 0]] {
+            grid[y * 16 + x] = 1;
+        }
+        renderer.set_cells(&grid);
+        renderer.step(8);
+        let translating = Event::Translating {
+            period: 4,
+            displacement: [1, -1],
+        };
+        assert_eq!(renderer.events(), [(5, translating)]);
+
+        // Under a rule where nothing changes, the glider stands still, and it is not mistaken for
+        // the phase it had when it was still flying.
+        renderer.step(1);
+        renderer.set_rule(&"B/S012345678".parse().unwrap());
+        renderer.step(3);
+        assert_eq!(renderer.events(), [(11, Event::Still)]);
+
+        // New cells start afresh too, dropping the events not handed out yet.
+        renderer.set_rule(&Rule::CONWAY);
+        renderer.step(4);
+        let mut grid = vec![0; 16 * 16];
+        grid[5 * 16 + 5..5 * 16 + 8].fill(1);
+        renderer.set_cells(&grid);
+        renderer.step(4);
+        let oscillating = Event::Oscillating { period: 2 };
+        assert_eq!(renderer.events(), [(19, oscillating)]);
+        assert_eq!(
+            renderer.detector().and_then(Detector::state),
+            Some(oscillating)
+        );
+    }
+}
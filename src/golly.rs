@@ -0,0 +1,725 @@
+//! Golly rule files: an `@RULE` name followed by an `@TABLE` or `@TREE` section and optionally
+//! `@COLORS`. Tables are compiled into rule trees, so both run on the GPU the same way.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    rule::Rule,
+    ruletree::{RuleTree, TreeBuilder},
+};
+
+/// Reads and compiles the rule file at `path`.
+pub fn load(path: &Path) -> Result<RuleTree> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse(&text).with_context(|| format!("invalid rule file {}", path.display()))
+}
+
+pub fn parse(text: &str) -> Result<RuleTree> {
+    let mut name = None;
+    let mut sections: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut section = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('@') {
+            let (tag, rest) = header
+                .split_once(char::is_whitespace)
+                .unwrap_or((header, ""));
+            if tag == "RULE" {
+                name = Some(rest.trim());
+            }
+            section = Some(tag);
+            sections.entry(tag).or_default();
+            continue;
+        }
+        // Comments start with `#`; other sections such as @ICONS are ignored.
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let (Some(section), false) = (section, line.is_empty()) {
+            sections.entry(section).or_default().push(line);
+        }
+    }
+
+    let name = name
+        .filter(|name| !name.is_empty())
+        .context("missing @RULE name")?;
+    let mut tree = match (sections.get("TABLE"), sections.get("TREE")) {
+        (Some(table), _) => parse_table(name, table).context("invalid @TABLE")?,
+        (None, Some(tree)) => parse_tree(name, tree).context("invalid @TREE")?,
+        (None, None) => bail!("expected a @TABLE or @TREE section"),
+    };
+    if let Some(colours) = sections.get("COLORS") {
+        parse_colours(colours, &mut tree.colours).context("invalid @COLORS")?;
+    }
+    Ok(tree)
+}
+
+/// Splits `key:value` or `key=value`.
+fn setting<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(key)?.trim_start();
+    let value = rest.strip_prefix([':', '='])?;
+    Some(value.trim())
+}
+
+fn parse_number(value: &str) -> Result<u32> {
+    value
+        .parse()
+        .with_context(|| format!("{value:?} is not a number"))
+}
+
+fn parse_states(value: &str) -> Result<u32> {
+    let states = parse_number(value)?;
+    ensure!(
+        (2..=Rule::MAX_STATES).contains(&states),
+        "the number of states must be between 2 and {}",
+        Rule::MAX_STATES
+    );
+    Ok(states)
+}
+
+/// A set of states as a bitmask.
+type StateSet = [u64; 4];
+
+fn set_contains(set: &StateSet, state: u32) -> bool {
+    set[state as usize / 64] & (1 << (state % 64)) != 0
+}
+
+fn set_of(states: &[u32]) -> StateSet {
+    let mut set = [0; 4];
+    for &state in states {
+        set[state as usize / 64] |= 1 << (state % 64);
+    }
+    set
+}
+
+/// How the cells of a table line map onto the cells the rule tree looks at.
+struct TableNeighbourhood {
+    /// Cells in a table line before the new state: the centre, then the neighbours clockwise.
+    cells: usize,
+    tree_neighbours: u32,
+    /// For each cell of the tree, in tree order, its position in a table line; `None` for the
+    /// cells of the tree's neighbourhood that the table ignores.
+    positions: &'static [Option<usize>],
+}
+
+const MOORE: TableNeighbourhood = TableNeighbourhood {
+    // C, N, NE, E, SE, S, SW, W, NW
+    cells: 9,
+    tree_neighbours: 8,
+    positions: &[
+        Some(8),
+        Some(2),
+        Some(6),
+        Some(4),
+        Some(1),
+        Some(7),
+        Some(3),
+        Some(5),
+        Some(0),
+    ],
+};
+
+const VON_NEUMANN: TableNeighbourhood = TableNeighbourhood {
+    // C, N, E, S, W
+    cells: 5,
+    tree_neighbours: 4,
+    positions: &[Some(1), Some(4), Some(2), Some(3), Some(0)],
+};
+
+/// Golly's hexagonal neighbourhood on the square grid: the Moore neighbourhood without NE and SW.
+const HEXAGONAL: TableNeighbourhood = TableNeighbourhood {
+    // C, N, E, SE, S, W, NW
+    cells: 7,
+    tree_neighbours: 8,
+    positions: &[
+        Some(6),
+        None,
+        None,
+        Some(3),
+        Some(1),
+        Some(5),
+        Some(2),
+        Some(4),
+        Some(0),
+    ],
+};
+
+/// A transition after variables are expanded: the allowed states of each cell and the new state.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Transition {
+    cells: Vec<StateSet>,
+    next: u32,
+}
+
+fn parse_table(name: &str, lines: &[&str]) -> Result<RuleTree> {
+    let mut states = None;
+    let mut neighbourhood = None;
+    let mut symmetries = "none";
+    let mut variables: HashMap<&str, Vec<u32>> = HashMap::new();
+    let mut transitions = Vec::new();
+
+    for &line in lines {
+        if let Some(value) = setting(line, "n_states") {
+            states = Some(parse_states(value)?);
+        } else if let Some(value) = setting(line, "neighborhood") {
+            neighbourhood = Some(match value {
+                "Moore" => &MOORE,
+                "vonNeumann" => &VON_NEUMANN,
+                "hexagonal" => &HEXAGONAL,
+                _ => bail!("unsupported neighborhood {value:?}"),
+            });
+        } else if let Some(value) = setting(line, "symmetries") {
+            symmetries = value;
+        } else if let Some(definition) = line.strip_prefix("var ") {
+            let states = states.context("n_states must come before the variables")?;
+            let (variable, values) = definition
+                .split_once('=')
+                .with_context(|| format!("invalid variable {line:?}"))?;
+            let values = values
+                .trim()
+                .strip_prefix('{')
+                .and_then(|values| values.strip_suffix('}'))
+                .with_context(|| format!("expected {{...}} in {line:?}"))?;
+            let mut set = Vec::new();
+            for value in values.split(',').map(str::trim) {
+                set.extend(parse_value(value, states, &variables)?);
+            }
+            variables.insert(variable.trim(), set);
+        } else {
+            let states = states.context("missing n_states")?;
+            let neighbourhood = neighbourhood.context("missing neighborhood")?;
+            let mut expanded = expand_variables(line, neighbourhood.cells, states, &variables)
+                .with_context(|| format!("invalid transition {line:?}"))?;
+            transitions.append(&mut expanded);
+        }
+    }
+
+    let states = states.context("missing n_states")?;
+    let neighbourhood = neighbourhood.context("missing neighborhood")?;
+    let transitions = apply_symmetries(transitions, neighbourhood.cells - 1, symmetries)?;
+
+    let mut builder = TreeBuilder::new(states);
+    let mut memo = HashMap::new();
+    let all = (0..transitions.len()).collect();
+    let root = build_table_tree(
+        &mut builder,
+        &mut memo,
+        &transitions,
+        neighbourhood.positions,
+        0,
+        all,
+    );
+    Ok(builder.finish(name, neighbourhood.tree_neighbours, root))
+}
+
+/// The states a state or variable in a table stands for.
+fn parse_value(value: &str, states: u32, variables: &HashMap<&str, Vec<u32>>) -> Result<Vec<u32>> {
+    if let Some(set) = variables.get(value) {
+        return Ok(set.clone());
+    }
+    let state = value
+        .parse::<u32>()
+        .with_context(|| format!("unknown variable {value:?}"))?;
+    ensure!(state < states, "state {state} is out of range");
+    Ok(vec![state])
+}
+
+/// Expands a transition line into transitions without repeated variables: a variable that occurs
+/// several times in a line takes the same value everywhere, including the new state.
+fn expand_variables(
+    line: &str,
+    cells: usize,
+    states: u32,
+    variables: &HashMap<&str, Vec<u32>>,
+) -> Result<Vec<Transition>> {
+    let mut tokens: Vec<&str> = line
+        .split([',', ' ', '\t'])
+        .filter(|token| !token.is_empty())
+        .collect();
+    // Rules with few states may leave out the commas, as in `0123`.
+    if let [token] = tokens[..] {
+        if token.chars().count() == cells + 1 {
+            tokens = token
+                .char_indices()
+                .map(|(i, c)| &token[i..i + c.len_utf8()])
+                .collect();
+        }
+    }
+    ensure!(
+        tokens.len() == cells + 1,
+        "expected {} states, got {}",
+        cells + 1,
+        tokens.len()
+    );
+
+    let mut repeated: Vec<&str> = Vec::new();
+    for (i, &token) in tokens.iter().enumerate() {
+        if variables.contains_key(token)
+            && tokens[..i].contains(&token)
+            && !repeated.contains(&token)
+        {
+            repeated.push(token);
+        }
+    }
+    let next = tokens[cells];
+    ensure!(
+        !variables.contains_key(next) || tokens[..cells].contains(&next),
+        "the new state {next:?} is not bound to a cell"
+    );
+
+    let mut transitions = Vec::new();
+    let mut bound = HashMap::new();
+    let mut expand = |bound: &HashMap<&str, u32>| -> Result<()> {
+        let value = |token: &str| match bound.get(token) {
+            Some(&state) => Ok(vec![state]),
+            None => parse_value(token, states, variables),
+        };
+        let cells = tokens[..cells]
+            .iter()
+            .map(|token| Ok(set_of(&value(token)?)))
+            .collect::<Result<_>>()?;
+        transitions.push(Transition {
+            cells,
+            next: value(next)?[0],
+        });
+        Ok(())
+    };
+
+    // Enumerates the values of the repeated variables like the digits of a counter.
+    let mut digits = vec![0; repeated.len()];
+    loop {
+        for (variable, &digit) in repeated.iter().zip(&digits) {
+            bound.insert(*variable, variables[variable][digit]);
+        }
+        expand(&bound)?;
+        let Some(i) = (0..digits.len()).find(|&i| digits[i] + 1 < variables[repeated[i]].len())
+        else {
+            break;
+        };
+        digits[i] += 1;
+        digits[..i].fill(0);
+    }
+    Ok(transitions)
+}
+
+/// Adds the images of every transition under the rule's symmetries, keeping the first transition
+/// that matches a neighbourhood first.
+fn apply_symmetries(
+    transitions: Vec<Transition>,
+    neighbours: usize,
+    symmetries: &str,
+) -> Result<Vec<Transition>> {
+    let mut seen = HashSet::new();
+    let mut images = Vec::new();
+    let mut add = |transition: Transition| {
+        if seen.insert(transition.clone()) {
+            images.push(transition);
+        }
+    };
+
+    if symmetries == "permute" {
+        for transition in transitions {
+            // Every distinct arrangement of the neighbours, in lexicographic order.
+            let mut cells = transition.cells.clone();
+            cells[1..].sort();
+            loop {
+                add(Transition {
+                    cells: cells.clone(),
+                    next: transition.next,
+                });
+                if !next_permutation(&mut cells[1..]) {
+                    break;
+                }
+            }
+        }
+        return Ok(images);
+    }
+
+    let (rotations, reflect) = match symmetries {
+        "none" => (1, false),
+        "reflect_horizontal" => (1, true),
+        _ => {
+            let rotations = symmetries.strip_prefix("rotate").unwrap_or_default();
+            let (rotations, reflect) = match rotations.strip_suffix("reflect") {
+                Some(rotations) => (rotations, true),
+                None => (rotations, false),
+            };
+            match rotations.parse::<usize>() {
+                Ok(rotations) if rotations > 0 && neighbours.is_multiple_of(rotations) => {
+                    (rotations, reflect)
+                }
+                _ => bail!("unsupported symmetries {symmetries:?}"),
+            }
+        }
+    };
+    let step = neighbours / rotations;
+    for transition in transitions {
+        for rotation in 0..rotations {
+            for reflected in [false, true].into_iter().take(1 + usize::from(reflect)) {
+                let mut cells = transition.cells.clone();
+                for i in 0..neighbours {
+                    let j = if reflected {
+                        (neighbours - i) % neighbours
+                    } else {
+                        i
+                    };
+                    cells[1 + (j + rotation * step) % neighbours] = transition.cells[1 + i];
+                }
+                add(Transition {
+                    cells,
+                    next: transition.next,
+                });
+            }
+        }
+    }
+    Ok(images)
+}
+
+/// Rearranges `items` into the next permutation in lexicographic order, or returns false if they
+/// are in the last one.
+fn next_permutation<T: Ord>(items: &mut [T]) -> bool {
+    let Some(i) = (1..items.len()).rev().find(|&i| items[i - 1] < items[i]) else {
+        return false;
+    };
+    let j = (i..items.len())
+        .rev()
+        .find(|&j| items[i - 1] < items[j])
+        .expect("items[i] is larger");
+    items.swap(i - 1, j);
+    items[i..].reverse();
+    true
+}
+
+/// Builds the subtree looking at the cells from `depth` on, given the transitions still matching
+/// the cells before it. Cells no transition matches keep their state.
+fn build_table_tree(
+    builder: &mut TreeBuilder,
+    memo: &mut HashMap<(usize, Vec<usize>), u32>,
+    transitions: &[Transition],
+    positions: &[Option<usize>],
+    depth: usize,
+    candidates: Vec<usize>,
+) -> u32 {
+    let key = (depth, candidates);
+    if let Some(&node) = memo.get(&key) {
+        return node;
+    }
+    let (_, candidates) = &key;
+    let matching = |state: u32| {
+        candidates
+            .iter()
+            .copied()
+            .filter(move |&t| match positions[depth] {
+                Some(position) => set_contains(&transitions[t].cells[position], state),
+                None => true,
+            })
+    };
+
+    let children = if depth + 1 == positions.len() {
+        (0..builder.states)
+            .map(|state| {
+                matching(state)
+                    .next()
+                    .map_or(state, |t| transitions[t].next)
+            })
+            .collect()
+    } else {
+        (0..builder.states)
+            .map(|state| {
+                let remaining = matching(state).collect();
+                build_table_tree(builder, memo, transitions, positions, depth + 1, remaining)
+            })
+            .collect()
+    };
+    let node = builder.node(positions.len() - depth, children);
+    memo.insert(key, node);
+    node
+}
+
+fn parse_tree(name: &str, lines: &[&str]) -> Result<RuleTree> {
+    let mut states = None;
+    let mut neighbours = None;
+    let mut builder = None;
+    // Offset of each node listed so far in the builder, and its level.
+    let mut nodes: Vec<(u32, usize)> = Vec::new();
+
+    for &line in lines {
+        if let Some(value) = setting(line, "num_states") {
+            ensure!(builder.is_none(), "num_states must come before the nodes");
+            states = Some(parse_states(value)?);
+        } else if let Some(value) = setting(line, "num_neighbors") {
+            let value = parse_number(value)?;
+            ensure!(
+                value == 4 || value == 8,
+                "num_neighbors must be 4 or 8, got {value}"
+            );
+            neighbours = Some(value);
+        } else if setting(line, "num_nodes").is_some() {
+            // The nodes are counted as they are read.
+        } else {
+            let states = states.context("missing num_states")?;
+            let builder = builder.get_or_insert_with(|| TreeBuilder::new(states));
+            let numbers = line
+                .split_whitespace()
+                .map(parse_number)
+                .collect::<Result<Vec<_>>>()?;
+            let Some((&level, children)) = numbers.split_first() else {
+                continue;
+            };
+            let level = level as usize;
+            ensure!(
+                children.len() == states as usize,
+                "node {line:?} needs {states} children"
+            );
+            let children = children
+                .iter()
+                .map(|&child| {
+                    if level == 1 {
+                        ensure!(child < states, "state {child} is out of range");
+                        return Ok(child);
+                    }
+                    match nodes.get(child as usize) {
+                        Some(&(offset, child_level)) if child_level + 1 == level => Ok(offset),
+                        _ => bail!("node {line:?} refers to an invalid node {child}"),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+            nodes.push((builder.node(level, children), level));
+        }
+    }
+
+    let neighbours = neighbours.context("missing num_neighbors")?;
+    let builder = builder.context("the tree has no nodes")?;
+    let &(root, level) = nodes.last().context("the tree has no nodes")?;
+    ensure!(
+        level == neighbours as usize + 1,
+        "the root must be at level {}",
+        neighbours + 1
+    );
+    Ok(builder.finish(name, neighbours, root))
+}
+
+/// Reads `state r g b` lines, or `r g b r g b` for a gradient over the live states.
+fn parse_colours(lines: &[&str], colours: &mut [[u8; 3]]) -> Result<()> {
+    for &line in lines {
+        let numbers = line
+            .split_whitespace()
+            .map(|number| {
+                number
+                    .parse::<u8>()
+                    .with_context(|| format!("invalid number {number:?}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        match numbers[..] {
+            [state, r, g, b] => {
+                let colour = colours
+                    .get_mut(state as usize)
+                    .with_context(|| format!("state {state} is out of range"))?;
+                *colour = [r, g, b];
+            }
+            [r0, g0, b0, r1, g1, b1] => {
+                let live = colours.len() - 1;
+                for (i, colour) in colours[1..].iter_mut().enumerate() {
+                    let mix = |from: u8, to: u8| {
+                        let t = if live > 1 {
+                            i as f32 / (live - 1) as f32
+                        } else {
+                            0.
+                        };
+                        (from as f32 + (to as f32 - from as f32) * t).round() as u8
+                    };
+                    *colour = [mix(r0, r1), mix(g0, g1), mix(b0, b1)];
+                }
+            }
+            _ => bail!("expected state r g b or r g b r g b, got {line:?}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::ruletree::RuleTree;
+
+    /// Every neighbourhood of a rule, in tree order.
+    fn neighbourhoods(tree: &RuleTree) -> Vec<Vec<u32>> {
+        let cells = tree.neighbours as usize + 1;
+        (0..tree.states.pow(cells as u32))
+            .map(|mut index| {
+                (0..cells)
+                    .map(|_| {
+                        let state = index % tree.states;
+                        index /= tree.states;
+                        state
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_tables() {
+        let life = parse(
+            "@RULE Life
+            A comment about the rule.
+            @TABLE
+            n_states:2
+            neighborhood:Moore
+            symmetries:permute
+            var a={0,1}
+            var b={a}
+            var c={a}
+            var d={a}
+            var e={a}
+            var f={a}
+            var g={a}
+            var h={a}
+            0111000001 # born with three neighbours
+            1,1,1,0,0,0,0,0,0,1
+            1,1,1,1,0,0,0,0,0,1
+            1,a,b,c,d,e,f,g,h,0
+            @ICONS
+            XPM",
+        )
+        .unwrap();
+        assert_eq!(
+            (life.name.as_str(), life.states, life.neighbours),
+            ("Life", 2, 8)
+        );
+        for cells in neighbourhoods(&life) {
+            let count = cells[..8].iter().sum::<u32>();
+            let alive = matches!((cells[8], count), (0, 3) | (1, 2 | 3));
+            assert_eq!(life.next(&cells), u32::from(alive), "{cells:?}");
+        }
+
+        // An empty cell next to a 1 and a 2 clockwise after it becomes a 1; with reflections
+        // allowed, the 2 may come first too. Tree order is N, W, E, S and the centre.
+        for (symmetries, reflect) in [("rotate4", false), ("rotate4reflect", true)] {
+            let tree = parse(&format!(
+                "@RULE Pairs
+                @TABLE
+                n_states=3
+                neighborhood=vonNeumann
+                symmetries={symmetries}
+                0,1,2,0,0,1"
+            ))
+            .unwrap();
+            assert_eq!(tree.neighbours, 4);
+            for cells in neighbourhoods(&tree) {
+                let clockwise = [cells[0], cells[2], cells[3], cells[1]];
+                let pair = (0..4).any(|i| {
+                    let (first, second) = (clockwise[i], clockwise[(i + 1) % 4]);
+                    let rest = [clockwise[(i + 2) % 4], clockwise[(i + 3) % 4]];
+                    let ordered = (first, second) == (1, 2) || reflect && (first, second) == (2, 1);
+                    ordered && rest == [0, 0]
+                });
+                let expected = if cells[4] == 0 && pair { 1 } else { cells[4] };
+                assert_eq!(tree.next(&cells), expected, "{symmetries} {cells:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn parses_trees() {
+        // Each cell takes the state of its north neighbour.
+        let tree = parse(
+            "@RULE North
+            @TREE
+            num_states=2
+            num_neighbors=4
+            num_nodes=9
+            1 0 0
+            1 1 1
+            2 0 0
+            2 1 1
+            3 2 2
+            3 3 3
+            4 4 4
+            4 5 5
+            5 6 7",
+        )
+        .unwrap();
+        assert_eq!((tree.states, tree.neighbours), (2, 4));
+        for cells in neighbourhoods(&tree) {
+            assert_eq!(tree.next(&cells), cells[0], "{cells:?}");
+        }
+    }
+
+    #[test]
+    fn parses_colours() {
+        let table = "@RULE Colours
+            @TABLE
+            n_states:4
+            neighborhood:vonNeumann
+            0,1,0,0,0,1
+            @COLORS";
+        let tree = parse(&format!("{table}\n1 255 0 0\n3 0 0 255")).unwrap();
+        assert_eq!(
+            tree.colours,
+            [[48, 48, 48], [255, 0, 0], [255, 127, 0], [0, 0, 255]]
+        );
+        let tree = parse(&format!("{table}\n0 0 0 255 255 255\n0 10 20 30")).unwrap();
+        assert_eq!(
+            tree.colours,
+            [[10, 20, 30], [0, 0, 0], [128, 128, 128], [255, 255, 255]]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let table = |body: &str| format!("@RULE Bad\n@TABLE\n{body}");
+        let tree = |body: &str| format!("@RULE Bad\n@TREE\n{body}");
+        let moore = "n_states:2\nneighborhood:Moore";
+        let von_neumann = "n_states:3\nneighborhood:vonNeumann";
+        for text in [
+            String::new(),
+            "@TABLE\nn_states:2".into(),
+            "@RULE\n@TABLE".into(),
+            "@RULE Bad\n@ICONS".into(),
+            table(""),
+            table("n_states:1\nneighborhood:Moore\n0,0,0,0,0,0,0,0,0,0"),
+            table("n_states:257\nneighborhood:Moore"),
+            table("n_states:x"),
+            table("n_states:2\nneighborhood:triangular"),
+            table("neighborhood:Moore\n0,0,0,0,0,0,0,0,0,0"),
+            table("neighborhood:Moore\nvar a={0,1}"),
+            table("n_states:2\n0,0,0,0,0,0,0,0,0,0"),
+            table(&format!("{moore}\n0,0,0,0,0,0,0,0,0")),
+            table(&format!("{moore}\n0,0,0,0,0,0,0,0,0,2")),
+            table(&format!("{moore}\n0,0,0,0,0,0,0,0,0,x")),
+            table(&format!("{moore}\n000000000é")),
+            table(&format!("{moore}\nvar a={{0,1}}\n0,0,0,0,0,0,0,0,0,a")),
+            table(&format!("{moore}\nvar a={{0,2}}")),
+            table(&format!("{moore}\nvar a={{}}")),
+            table(&format!("{moore}\nvar a=0,1")),
+            table(&format!("{moore}\nvar a")),
+            table(&format!("{moore}\nsymmetries:rotate3\n0,0,0,0,0,0,0,0,0,0")),
+            table(&format!("{moore}\nsymmetries:rotate0\n0,0,0,0,0,0,0,0,0,0")),
+            table(&format!("{moore}\nsymmetries:mirror\n0,0,0,0,0,0,0,0,0,0")),
+            table(&format!("{von_neumann}\nsymmetries:rotate8\n0,0,0,0,0,0")),
+            table(&format!("{von_neumann}\n0,0,0,0,0,0\n@COLORS\n3 255 0 0")),
+            table(&format!("{von_neumann}\n0,0,0,0,0,0\n@COLORS\n1 255 0")),
+            table(&format!("{von_neumann}\n0,0,0,0,0,0\n@COLORS\n1 256 0 0")),
+            tree(""),
+            tree("num_states=2\nnum_neighbors=6\n1 0 0"),
+            tree("num_states=2\nnum_neighbors=4"),
+            tree("num_neighbors=4\n1 0 1"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 2"),
+            tree("num_states=2\nnum_neighbors=4\n1 0"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 x"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 1\n2 0 1"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 1\n3 0 0"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 1\n0 0 0"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 1\n2 0 0"),
+            tree("num_states=2\nnum_neighbors=4\n1 0 1\nnum_states=3\n1 0 1 2"),
+        ] {
+            assert!(parse(&text).is_err(), "{text:?}");
+        }
+    }
+}
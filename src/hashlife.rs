@@ -0,0 +1,517 @@
+//! Bill Gosper's HashLife: the universe is a quadtree whose identical subtrees are stored once,
+//! and the result of running each node for a power of two generations is remembered, so patterns
+//! with repetitive structure can be run for billions of generations.
+//!
+//! A node of level `k` covers `2^k` by `2^k` cells. Advancing it by `2^j` generations, for
+//! `j <= k - 2`, yields its centre node of level `k - 1`, built recursively from the results of
+//! its nine overlapping subnodes; level 2 nodes are advanced by applying the rule directly.
+
+use std::collections::HashMap;
+
+use crate::{
+    rule::{Neighbourhood, Rule},
+    simulation::Simulation,
+};
+
+/// Nodes kept before the memoised results are dropped and unreachable nodes collected.
+const MAX_NODES: usize = 1 << 21;
+
+/// Furthest generation HashLife runs to. Patterns spread by at most a cell a generation, and the
+/// root holds them in its middle quarter, so up to here its corners stay well within `i64`.
+pub const MAX_GENERATION: u64 = 1 << 56;
+
+#[derive(Copy, Clone)]
+struct Node {
+    /// The NW, NE, SW and SE quadrants; unused for the two cells of level 0.
+    children: [u32; 4],
+    level: u32,
+    population: u64,
+}
+
+/// An unbounded universe of a two-state rule with a range 1 neighbourhood. The root node is
+/// centred on the origin.
+pub struct HashLife {
+    /// New state of the centre of each 3x3 neighbourhood; bit `3 * row + column` of the index is
+    /// the cell in that row (from the top) and column.
+    table: [u8; 512],
+    nodes: Vec<Node>,
+    ids: HashMap<[u32; 4], u32>,
+    /// The empty node of each level.
+    empty: Vec<u32>,
+    /// The centre of a node after `2^j` generations, by node and `j`.
+    results: HashMap<(u32, u32), u32>,
+    root: u32,
+    generation: u64,
+}
+
+impl HashLife {
+    /// Whether HashLife can run `rule`: it only knows two states and the cells next to each cell.
+    pub fn supports(rule: &Rule) -> bool {
+        rule.states == 2 && rule.radius == 1
+    }
+
+    /// A universe of `rule` holding `cells`, a `[width, height]` region with its bottom left corner
+    /// at `origin`, listed row by row from the bottom.
+    pub fn new(
+        rule: &Rule,
+        origin: [i64; 2],
+        [width, height]: [u32; 2],
+        cells: &[u32],
+    ) -> HashLife {
+        let mut hashlife = HashLife {
+            table: [0; 512],
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            empty: Vec::new(),
+            results: HashMap::new(),
+            root: 0,
+            generation: 0,
+        };
+        for population in 0..2 {
+            hashlife.nodes.push(Node {
+                children: [0; 4],
+                level: 0,
+                population,
+            });
+        }
+        hashlife.set_rule(rule);
+        hashlife.root = hashlife.empty(3);
+
+        for y in 0..height {
+            for x in 0..width {
+                if cells[(y * width + x) as usize] != 0 {
+                    let cell = [origin[0] + i64::from(x), origin[1] + i64::from(y)];
+                    hashlife.set_cell(cell);
+                }
+            }
+        }
+        hashlife
+    }
+
+    fn level(&self, node: u32) -> u32 {
+        self.nodes[node as usize].level
+    }
+
+    fn children(&self, node: u32) -> [u32; 4] {
+        self.nodes[node as usize].children
+    }
+
+    /// The node with the given quadrants.
+    fn node(&mut self, children: [u32; 4]) -> u32 {
+        if let Some(&id) = self.ids.get(&children) {
+            return id;
+        }
+        let id = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            children,
+            level: self.level(children[0]) + 1,
+            population: children
+                .iter()
+                .map(|&child| self.nodes[child as usize].population)
+                .sum(),
+        });
+        self.ids.insert(children, id);
+        id
+    }
+
+    fn empty(&mut self, level: u32) -> u32 {
+        while self.empty.len() <= level as usize {
+            let node = match self.empty.last() {
+                Some(&child) => self.node([child; 4]),
+                None => 0,
+            };
+            self.empty.push(node);
+        }
+        self.empty[level as usize]
+    }
+
+    /// The node of the next level down at the centre of `node`.
+    fn centre(&mut self, node: u32) -> u32 {
+        let [nw, ne, sw, se] = self.children(node).map(|child| self.children(child));
+        self.node([nw[3], ne[2], sw[1], se[0]])
+    }
+
+    /// The grandchildren of `node` as a 4x4 grid, rows from the top.
+    fn grandchildren(&self, node: u32) -> [[u32; 4]; 4] {
+        let [nw, ne, sw, se] = self.children(node).map(|child| self.children(child));
+        [
+            [nw[0], nw[1], ne[0], ne[1]],
+            [nw[2], nw[3], ne[2], ne[3]],
+            [sw[0], sw[1], se[0], se[1]],
+            [sw[2], sw[3], se[2], se[3]],
+        ]
+    }
+
+    /// The centre of `node` after `2^j` generations.
+    fn advance(&mut self, node: u32, j: u32) -> u32 {
+        let level = self.level(node);
+        debug_assert!(j + 2 <= level);
+        if self.nodes[node as usize].population == 0 {
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(node, j)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.advance_cells(node)
+        } else {
+            // Nine overlapping subnodes are run for up to half the time (or just cut down to their
+            // centres), then the four overlapping squares of their results for the rest of it.
+            let g = self.grandchildren(node);
+            let full_speed = j + 2 == level;
+            let mut partial = [[0; 3]; 3];
+            for (row, partial) in partial.iter_mut().enumerate() {
+                for (column, partial) in partial.iter_mut().enumerate() {
+                    let sub = self.node([
+                        g[row][column],
+                        g[row][column + 1],
+                        g[row + 1][column],
+                        g[row + 1][column + 1],
+                    ]);
+                    *partial = match full_speed {
+                        true => self.advance(sub, j - 1),
+                        false => self.centre(sub),
+                    };
+                }
+            }
+            let rest = if full_speed { j - 1 } else { j };
+            let mut quadrants = [0; 4];
+            for (quadrant, [row, column]) in
+                [[0, 0], [0, 1], [1, 0], [1, 1]].into_iter().enumerate()
+            {
+                let square = self.node([
+                    partial[row][column],
+                    partial[row][column + 1],
+                    partial[row + 1][column],
+                    partial[row + 1][column + 1],
+                ]);
+                quadrants[quadrant] = self.advance(square, rest);
+            }
+            self.node(quadrants)
+        };
+        self.results.insert((node, j), result);
+        result
+    }
+
+    /// Runs the rule on the centre 2x2 cells of a level 2 node.
+    fn advance_cells(&mut self, node: u32) -> u32 {
+        let g = self.grandchildren(node);
+        let mut next = [0; 4];
+        for (i, [row, column]) in [[1, 1], [1, 2], [2, 1], [2, 2]].into_iter().enumerate() {
+            let mut index = 0;
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    index |= (g[row + dy - 1][column + dx - 1] as usize) << (3 * dy + dx);
+                }
+            }
+            next[i] = u32::from(self.table[index]);
+        }
+        self.node(next)
+    }
+
+    /// Surrounds the root with empty space, doubling its size.
+    fn expand(&mut self) {
+        let [nw, ne, sw, se] = self.children(self.root);
+        let e = self.empty(self.level(self.root) - 1);
+        let children = [
+            self.node([e, e, e, nw]),
+            self.node([e, e, ne, e]),
+            self.node([e, sw, e, e]),
+            self.node([se, e, e, e]),
+        ];
+        self.root = self.node(children);
+    }
+
+    /// Whether all live cells lie in the middle quarter of the root's width, from where they
+    /// cannot leave its centre within a quarter of its width in generations.
+    fn is_padded(&mut self) -> bool {
+        let centre = self.centre(self.root);
+        let middle = self.centre(centre);
+        self.nodes[middle as usize].population == self.population()
+    }
+
+    /// Half the width of the root, the distance from the origin to its edges.
+    fn radius(&self) -> i64 {
+        1 << (self.level(self.root) - 1)
+    }
+
+    fn set_cell(&mut self, [x, y]: [i64; 2]) {
+        while x < -self.radius() || x >= self.radius() || y < -self.radius() || y >= self.radius() {
+            self.expand();
+        }
+        let r = self.radius();
+        self.root = self.set_in(self.root, [x + r, y + r]);
+    }
+
+    /// `node` with the cell at `[x, y]` from its bottom left corner alive.
+    fn set_in(&mut self, node: u32, [x, y]: [i64; 2]) -> u32 {
+        let level = self.level(node);
+        if level == 0 {
+            return 1;
+        }
+        let half = 1 << (level - 1);
+        let quadrant = usize::from(x >= half) + 2 * usize::from(y < half);
+        let mut children = self.children(node);
+        children[quadrant] = self.set_in(children[quadrant], [x % half, y % half]);
+        self.node(children)
+    }
+
+    /// Copies the cells of `node`, whose bottom left corner is at `corner`, into `cells`, the
+    /// region of size `[width, height]` at `origin`.
+    fn fill(
+        &self,
+        node: u32,
+        corner: [i64; 2],
+        cells: &mut [u32],
+        origin: [i64; 2],
+        [width, height]: [i64; 2],
+    ) {
+        let Node {
+            children,
+            level,
+            population,
+        } = self.nodes[node as usize];
+        let size = 1 << level;
+        let outside = corner[0] >= origin[0] + width
+            || corner[1] >= origin[1] + height
+            || corner[0] + size <= origin[0]
+            || corner[1] + size <= origin[1];
+        if population == 0 || outside {
+            return;
+        }
+        if level == 0 {
+            let [x, y] = [corner[0] - origin[0], corner[1] - origin[1]];
+            cells[(y * width + x) as usize] = 1;
+            return;
+        }
+        let half = size / 2;
+        let corners = [[0, half], [half, half], [0, 0], [half, 0]];
+        for (child, [dx, dy]) in children.into_iter().zip(corners) {
+            let corner = [corner[0] + dx, corner[1] + dy];
+            self.fill(child, corner, cells, origin, [width, height]);
+        }
+    }
+
+    /// Drops the remembered results and the nodes the root no longer uses.
+    fn collect_garbage(&mut self) {
+        let old = std::mem::take(&mut self.nodes);
+        self.nodes = old[..2].to_vec();
+        self.ids.clear();
+        self.empty.clear();
+        self.results.clear();
+        let mut copies = HashMap::from([(0, 0), (1, 1)]);
+        self.root = self.copy(&old, self.root, &mut copies);
+    }
+
+    fn copy(&mut self, old: &[Node], node: u32, copies: &mut HashMap<u32, u32>) -> u32 {
+        if let Some(&copy) = copies.get(&node) {
+            return copy;
+        }
+        let children = old[node as usize]
+            .children
+            .map(|child| self.copy(old, child, copies));
+        let copy = self.node(children);
+        copies.insert(node, copy);
+        copy
+    }
+
+    /// Advances by `2^j` generations.
+    fn step_power_of_two(&mut self, j: u32) {
+        if self.nodes.len() > MAX_NODES {
+            self.collect_garbage();
+        }
+        while self.level(self.root) < j + 3 || !self.is_padded() {
+            self.expand();
+        }
+        self.root = self.advance(self.root, j);
+        self.generation += 1 << j;
+    }
+}
+
+impl Simulation for HashLife {
+    fn set_rule(&mut self, rule: &Rule) {
+        let transitions = rule
+            .isotropic
+            .as_ref()
+            .map(|isotropic| isotropic.transitions());
+        for (index, next) in self.table.iter_mut().enumerate() {
+            let cell = |row: usize, column: usize| (index >> (3 * row + column)) as u32 & 1;
+            let centre = cell(1, 1);
+            *next = if let Some(tree) = &rule.tree {
+                let mut cells = match tree.neighbours {
+                    8 => vec![cell(0, 0), cell(0, 2), cell(2, 0), cell(2, 2)],
+                    _ => Vec::new(),
+                };
+                cells.extend([cell(0, 1), cell(1, 0), cell(1, 2), cell(2, 1), centre]);
+                tree.next(&cells) as u8
+            } else if let Some(transitions) = &transitions {
+                // Live neighbours clockwise from N, as the transition table is indexed.
+                let configuration = [
+                    (0, 1),
+                    (0, 2),
+                    (1, 2),
+                    (2, 2),
+                    (2, 1),
+                    (2, 0),
+                    (1, 0),
+                    (0, 0),
+                ]
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (bit, &(row, column))| {
+                    bits | cell(row, column) << bit
+                });
+                let transition = transitions[configuration as usize];
+                u8::from(transition >> centre & 1 == 1)
+            } else {
+                let neighbours: &[(usize, usize)] = match rule.neighbourhood {
+                    Neighbourhood::Moore => &[
+                        (0, 0),
+                        (0, 1),
+                        (0, 2),
+                        (1, 0),
+                        (1, 2),
+                        (2, 0),
+                        (2, 1),
+                        (2, 2),
+                    ],
+                    Neighbourhood::VonNeumann => &[(0, 1), (1, 0), (1, 2), (2, 1)],
+                    // Golly's emulation on the square grid: NW, N, W, E, S, SE.
+                    Neighbourhood::Hexagonal => &[(0, 0), (0, 1), (1, 0), (1, 2), (2, 1), (2, 2)],
+                };
+                let mut count: u32 = neighbours
+                    .iter()
+                    .map(|&(row, column)| cell(row, column))
+                    .sum();
+                if rule.include_centre {
+                    count += centre;
+                }
+                let counts = if centre == 1 {
+                    &rule.survival
+                } else {
+                    &rule.birth
+                };
+                u8::from(counts.contains(count))
+            };
+        }
+        self.results.clear();
+    }
+
+    /// Stops at [`MAX_GENERATION`].
+    fn step(&mut self, generations: u64) {
+        let generations = generations.min(MAX_GENERATION - self.generation);
+        for j in 0..u64::BITS {
+            if generations >> j & 1 == 1 {
+                self.step_power_of_two(j);
+            }
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    fn region(&self, origin: [i64; 2], [width, height]: [u32; 2]) -> Vec<u32> {
+        let mut cells = vec![0; (width * height) as usize];
+        let r = self.radius();
+        let size = [i64::from(width), i64::from(height)];
+        self.fill(self.root, [-r, -r], &mut cells, origin, size);
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{HashLife, MAX_GENERATION};
+    use crate::{render::Renderer, simulation::Simulation, Config};
+
+    /// A glider heading down and to the right, a cell every four generations.
+    const GLIDER: [[i64; 2]; 5] = [[1, 2], [2, 1], [0, 0], [1, 0], [2, 0]];
+
+    fn glider_at(hashlife: &HashLife, [x, y]: [i64; 2]) -> bool {
+        let cells = hashlife.region([x, y], [3, 3]);
+        let glider = GLIDER.iter().map(|&[x, y]| (y * 3 + x) as usize);
+        cells.iter().sum::<u32>() == 5 && glider.into_iter().all(|i| cells[i] == 1)
+    }
+
+    #[test]
+    fn gliders_reach_distant_generations() {
+        let mut cells = vec![0; 9];
+        for [x, y] in GLIDER {
+            cells[(y * 3 + x) as usize] = 1;
+        }
+        let mut hashlife = HashLife::new(&"B3/S23".parse().unwrap(), [0, 0], [3, 3], &cells);
+        hashlife.step(1024);
+        assert!(glider_at(&hashlife, [256, -256]));
+
+        hashlife.step((1 << 40) - 1024);
+        assert_eq!(hashlife.generation(), 1 << 40);
+        assert_eq!(hashlife.population(), 5);
+        let distance = 1 << 38;
+        assert!(glider_at(&hashlife, [distance, -distance]));
+
+        // As far as HashLife goes, and no further.
+        hashlife.step(MAX_GENERATION - (1 << 40));
+        assert_eq!(hashlife.generation(), MAX_GENERATION);
+        let distance = (MAX_GENERATION / 4) as i64;
+        assert!(glider_at(&hashlife, [distance, -distance]));
+        hashlife.step(1 << 40);
+        assert_eq!(hashlife.generation(), MAX_GENERATION);
+        assert!(glider_at(&hashlife, [distance, -distance]));
+    }
+
+    #[test]
+    fn replicators_reach_the_last_generation() {
+        // B1 rules spread a cell a generation in every direction, as fast as anything can.
+        let rule = "B1357/S1357".parse().unwrap();
+        let mut hashlife = HashLife::new(&rule, [0, 0], [1, 1], &[1]);
+        hashlife.step(MAX_GENERATION);
+        assert_eq!(hashlife.generation(), MAX_GENERATION);
+        // At generation 2^k, the cell has left eight copies of itself 2^k cells away.
+        assert_eq!(hashlife.population(), 8);
+        let far = MAX_GENERATION as i64;
+        for x in [-far, 0, far] {
+            for y in [-far, 0, far] {
+                let copy = u32::from([x, y] != [0, 0]);
+                let expected = [0, 0, 0, 0, copy, 0, 0, 0, 0];
+                assert_eq!(hashlife.region([x - 1, y - 1], [3, 3]), expected, "{x},{y}");
+            }
+        }
+    }
+
+    /// Runs a soup with HashLife and on the GPU and compares the results around it.
+    fn check(rule: &str, generations: u64) {
+        let args = ["--boundary", "P0,0", "--grid-size", "24", "--rule", rule];
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        let Some(mut renderer) = Renderer::headless(&config) else {
+            return;
+        };
+        let mut rng = StdRng::seed_from_u64(13);
+        let soup: Vec<u32> = (0..24 * 24).map(|_| u32::from(rng.gen_bool(0.4))).collect();
+        renderer.set_cells(&soup);
+        let mut hashlife = HashLife::new(&config.rule, [0, 0], [24, 24], &soup);
+
+        renderer.step(generations);
+        hashlife.step(generations);
+        let window = ([-100, -100], [224, 224]);
+        let expected = renderer.region(window.0, window.1);
+        assert_eq!(hashlife.region(window.0, window.1), expected, "{rule}");
+        let population = expected.iter().sum::<u32>();
+        assert_eq!(hashlife.population(), u64::from(population), "{rule}");
+    }
+
+    #[test]
+    fn matches_the_gpu() {
+        check("B3/S23", 77);
+        check("B36/S23", 50);
+        check("B2-a/S12", 30);
+        check("R1,C0,M1,S3..4,B3,NN", 30);
+    }
+}
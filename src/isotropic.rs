@@ -0,0 +1,286 @@
+//! Isotropic non-totalistic rules in Hensel notation, e.g. `B2-a/S12` or `B3/S2-i34q`.
+//!
+//! A neighbourhood configuration is an 8-bit mask of the live Moore neighbours, bit `i` standing
+//! for the `i`-th of N, NE, E, SE, S, SW, W, NW. Hensel notation splits each neighbour count into
+//! the classes of configurations that are equal up to rotation and reflection and names each
+//! class with a letter.
+
+use std::{fmt, sync::OnceLock};
+
+use anyhow::{bail, ensure, Result};
+
+/// Letters of the configuration classes of each neighbour count, in Hensel's order.
+const LETTERS: [&str; 9] = [
+    "",
+    "ce",
+    "ceaikn",
+    "ceaiknjqry",
+    "ceaiknjqrtwyz",
+    "ceaiknjqry",
+    "ceaikn",
+    "ce",
+    "",
+];
+
+/// One configuration of each class with up to four neighbours, as N, NE, E, SE, S, SW, W, NW.
+/// Classes with more neighbours are the complements of these: `5a` is the complement of `3a`.
+const REPRESENTATIVES: [(&str, &str); 31] = [
+    ("1c", "01000000"),
+    ("1e", "10000000"),
+    ("2c", "01010000"),
+    ("2e", "10100000"),
+    ("2a", "11000000"),
+    ("2i", "10001000"),
+    ("2k", "10010000"),
+    ("2n", "01000100"),
+    ("3c", "01010100"),
+    ("3e", "10101000"),
+    ("3a", "11100000"),
+    ("3i", "11000001"),
+    ("3k", "10100100"),
+    ("3n", "11010000"),
+    ("3j", "11000010"),
+    ("3q", "11000100"),
+    ("3r", "11001000"),
+    ("3y", "10010100"),
+    ("4c", "01010101"),
+    ("4e", "10101010"),
+    ("4a", "11110000"),
+    ("4i", "11011000"),
+    ("4k", "11010010"),
+    ("4n", "11010001"),
+    ("4j", "11001010"),
+    ("4q", "11100100"),
+    ("4r", "11101000"),
+    ("4t", "11001001"),
+    ("4w", "11000110"),
+    ("4y", "11010100"),
+    ("4z", "11001100"),
+];
+
+/// A set of neighbourhood configurations, bit `c` standing for configuration `c`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Configurations(pub [u32; 8]);
+
+impl Configurations {
+    pub fn contains(&self, configuration: u8) -> bool {
+        self.0[configuration as usize / 32] & (1 << (configuration % 32)) != 0
+    }
+
+    fn insert(&mut self, configuration: u8) {
+        self.0[configuration as usize / 32] |= 1 << (configuration % 32);
+    }
+
+    /// Parses the conditions of one half of a rulestring, e.g. the `2-a` of `B2-a/S12`.
+    pub fn parse(rulestring: &str, conditions: &str) -> Result<Configurations> {
+        let mut set = Configurations::default();
+        let mut chars = conditions.chars().peekable();
+        while let Some(c) = chars.next() {
+            let Some(count @ 0..=8) = c.to_digit(10) else {
+                bail!("invalid rulestring {rulestring:?}: {c:?} is not a neighbour count between 0 and 8")
+            };
+            let negated = chars.next_if_eq(&'-').is_some();
+            let mut letters = String::new();
+            while let Some(letter) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                ensure!(
+                    LETTERS[count as usize].contains(letter),
+                    "invalid rulestring {rulestring:?}: {count}{letter} is not a Hensel class"
+                );
+                letters.push(letter);
+            }
+            ensure!(
+                !negated || !letters.is_empty(),
+                "invalid rulestring {rulestring:?}: expected letters after {count}-"
+            );
+
+            for configuration in 0..=255u8 {
+                let (class_count, letter) = classify(configuration);
+                if class_count != count {
+                    continue;
+                }
+                let listed = letter.is_some_and(|letter| letters.contains(letter));
+                if letters.is_empty() || listed != negated {
+                    set.insert(configuration);
+                }
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl fmt::Display for Configurations {
+    /// Writes the shortest Hensel notation of the set.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for count in 0..=8u32 {
+            let all = LETTERS[count as usize];
+            let mut present = String::new();
+            let mut any = false;
+            let mut complete = true;
+            for letter in all.chars().map(Some).chain(all.is_empty().then_some(None)) {
+                let in_set = (0..=255u8)
+                    .filter(|&configuration| classify(configuration) == (count, letter))
+                    .all(|configuration| self.contains(configuration));
+                any |= in_set;
+                complete &= in_set;
+                if let (true, Some(letter)) = (in_set, letter) {
+                    present.push(letter);
+                }
+            }
+            if !any {
+                continue;
+            }
+            write!(f, "{count}")?;
+            if complete {
+                continue;
+            }
+            if present.len() * 2 <= all.len() {
+                write!(f, "{present}")?;
+            } else {
+                let missing: String = all.chars().filter(|&c| !present.contains(c)).collect();
+                write!(f, "-{missing}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Birth and survival conditions of an isotropic non-totalistic rule.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Isotropic {
+    pub birth: Configurations,
+    pub survival: Configurations,
+}
+
+impl Isotropic {
+    /// The transition table uploaded to the GPU: bit 0 of entry `c` is set if a dead cell with
+    /// neighbourhood configuration `c` is born, bit 1 if a live one survives.
+    pub fn transitions(&self) -> [u32; 256] {
+        std::array::from_fn(|configuration| {
+            let configuration = configuration as u8;
+            u32::from(self.birth.contains(configuration))
+                | u32::from(self.survival.contains(configuration)) << 1
+        })
+    }
+}
+
+/// The neighbour count and Hensel letter of a configuration (no letter for 0 and 8 neighbours).
+pub fn classify(configuration: u8) -> (u32, Option<char>) {
+    static CLASSES: OnceLock<[Option<char>; 256]> = OnceLock::new();
+    let classes = CLASSES.get_or_init(|| {
+        let mut classes = [None; 256];
+        for (name, neighbours) in REPRESENTATIVES {
+            let representative =
+                u8::from_str_radix(&neighbours.chars().rev().collect::<String>(), 2)
+                    .expect("representatives are binary");
+            let letter = name.chars().nth(1);
+            for configuration in symmetries(representative) {
+                classes[configuration as usize] = letter;
+                if configuration.count_ones() < 4 {
+                    classes[!configuration as usize] = letter;
+                }
+            }
+        }
+        classes
+    });
+    (configuration.count_ones(), classes[configuration as usize])
+}
+
+/// The images of a configuration under the eight rotations and reflections of the square.
+fn symmetries(configuration: u8) -> impl Iterator<Item = u8> {
+    (0..8).map(move |symmetry: u32| {
+        (0..8).fold(0u8, |image, i| {
+            let reflected = if symmetry >= 4 { (8 - i) % 8 } else { i };
+            let j = (reflected + 2 * (symmetry % 4)) % 8;
+            image | ((configuration >> i) & 1) << j
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{classify, symmetries, Configurations, LETTERS};
+    use crate::rule::Rule;
+
+    #[test]
+    fn classes_partition_the_configurations() {
+        let mut classes: HashMap<(u32, Option<char>), HashSet<u8>> = HashMap::new();
+        for configuration in 0..=255u8 {
+            let (count, letter) = classify(configuration);
+            let class = (count, letter);
+            assert_eq!(count, configuration.count_ones());
+            assert_eq!(
+                letter.is_none(),
+                count == 0 || count == 8,
+                "{configuration:08b}"
+            );
+            // A class is closed under the symmetries of the square, and its complement has the
+            // same letter, except with four neighbours where e.g. 4c and 4e swap.
+            for image in symmetries(configuration) {
+                assert_eq!(classify(image), class, "{configuration:08b}");
+            }
+            if count != 4 {
+                assert_eq!(classify(!configuration).1, letter, "{configuration:08b}");
+            }
+            classes.entry(class).or_default().insert(configuration);
+        }
+
+        // Hensel's published number of classes for each neighbour count.
+        let per_count: Vec<usize> = (0..=8)
+            .map(|count| classes.keys().filter(|&&(n, _)| n == count).count())
+            .collect();
+        assert_eq!(per_count, [1, 2, 6, 10, 13, 10, 6, 2, 1]);
+        for (count, letters) in LETTERS.iter().enumerate() {
+            for letter in letters.chars() {
+                assert!(
+                    classes.contains_key(&(count as u32, Some(letter))),
+                    "{count}{letter}"
+                );
+            }
+        }
+
+        // Each class is a single orbit of the symmetries.
+        for (class, configurations) in &classes {
+            let first = *configurations.iter().next().unwrap();
+            let orbit: HashSet<u8> = symmetries(first).collect();
+            assert_eq!(&orbit, configurations, "{class:?}");
+        }
+        let size = |count, letter| classes[&(count, Some(letter))].len();
+        assert_eq!([size(1, 'c'), size(1, 'e')], [4, 4]);
+        assert_eq!([size(2, 'i'), size(2, 'a')], [2, 8]);
+        assert_eq!([size(4, 'c'), size(4, 'e')], [1, 1]);
+    }
+
+    #[test]
+    fn parses_hensel_notation() {
+        let parse = |rulestring: &str| rulestring.parse::<Rule>().unwrap();
+        for rulestring in ["B2-a/S12", "B3/S2-i34q", "B2ce3ai/S1e2-kn4z8", "B/S0"] {
+            let rule = parse(rulestring);
+            assert!(rule.isotropic.is_some() || rulestring == "B/S0");
+            assert_eq!(rule.to_string(), rulestring);
+            assert_eq!(parse(&rule.to_string()), rule);
+        }
+
+        let set = Configurations::parse("", "2-a").unwrap();
+        for configuration in 0..=255u8 {
+            let (count, letter) = classify(configuration);
+            assert_eq!(
+                set.contains(configuration),
+                count == 2 && letter != Some('a'),
+                "{configuration:08b}"
+            );
+        }
+        // Listing every class is the same as listing none.
+        assert_eq!(
+            Configurations::parse("", "3ceaiknjqry").unwrap(),
+            Configurations::parse("", "3").unwrap()
+        );
+
+        for invalid in [
+            "B2x/S23", "B1a/S", "B0c/S", "B8e/S", "B3-/S23", "B2a/S9", "B2a/S1-",
+        ] {
+            assert!(invalid.parse::<Rule>().is_err(), "{invalid:?}");
+        }
+    }
+}
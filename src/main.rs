@@ -1,25 +1,498 @@
 use {
-    anyhow::{Context, Result}, std::{thread, time::Duration}, winit::{
-        event::{Event, WindowEvent},
-        event_loop::{ControlFlow, EventLoop},
+    anyhow::{bail, ensure, Context, Result},
+    boundary::Boundary,
+    clock::Clock,
+    cpu::Cpu,
+    fingerprint::Detector,
+    hashlife::{HashLife, MAX_GENERATION},
+    overlay::Overlay,
+    render::Renderer,
+    rule::{Neighbourhood, Rule},
+    simulation::Simulation,
+    soup::Soup,
+    std::{
+        io::BufRead,
+        path::{Path, PathBuf},
+        thread,
+        time::Instant,
+    },
+    stencil::Stencil,
+    topology::Topology,
+    winit::{
+        event::{ElementState, Event, KeyEvent, WindowEvent},
+        event_loop::{ControlFlow, EventLoopBuilder},
+        keyboard::{Key, NamedKey},
         window::{Window, WindowBuilder},
-    }
+    },
 };
 
+mod boundary;
+mod chunks;
+mod clock;
+mod cpu;
+mod fingerprint;
+mod golly;
+mod hashlife;
+mod isotropic;
+mod objects;
+mod overlay;
+#[cfg(test)]
+mod reference;
 mod render;
+mod rule;
+mod ruletree;
+mod search;
+mod simulation;
+mod soup;
+mod stencil;
+mod topology;
+
+/// What is reported once an unbounded pattern no longer fits on the GPU.
+const OUTGROWN: &str =
+    "the pattern has outgrown the largest buffer the GPU can bind, cells beyond it are lost";
 
+#[derive(Clone)]
 pub struct Config {
     width: u32,
     height: u32,
-    grid_size: u32,
+    /// Width and height of the grid in cells; in an unbounded universe the size of the initial
+    /// soup and of the view.
+    grid_size: [u32; 2],
+    rule: Rule,
+    /// Replaces the rule's neighbourhood when set.
+    stencil: Option<Stencil>,
+    topology: Topology,
+    boundary: Boundary,
+    engine: Engine,
+    /// Generations advanced at a time.
+    step: u64,
+    /// Target generations per second.
+    speed: f64,
+    storage: Storage,
+    kernel: Kernel,
+    /// Whether each generation only computes the tiles within reach of last generation's changes.
+    active_tiles: bool,
+    /// Generations to time each kernel over instead of opening a window.
+    benchmark: Option<u64>,
+    /// Generations to run without a window, drawing the last one offscreen.
+    headless: Option<u64>,
+    /// Where a headless run writes its last frame, as a PPM image.
+    output: Option<PathBuf>,
+    /// Whether to run on a software adapter such as llvmpipe even when there is a GPU.
+    fallback_adapter: bool,
+    /// The initial cells.
+    soup: Soup,
+    /// Soups to search for objects instead of opening a window.
+    search: Option<u64>,
+    /// The census file a search adds to.
+    census: Option<PathBuf>,
+    /// Whether to box and label the objects in view.
+    overlay: bool,
+    /// Whether to fingerprint each generation and report when the universe dies out, stops
+    /// changing or repeats.
+    detect: bool,
+}
+
+/// What advances the universe.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Engine {
+    /// The compute shader.
+    Gpu,
+    /// HashLife on the CPU, with the GPU only drawing the cells in view.
+    HashLife,
+    /// The compute shader's rules run on the CPU's cores, for when the GPU can't run them.
+    Cpu,
+}
+
+/// How the GPU stores the cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Storage {
+    /// A `u32` state per cell.
+    Cells,
+    /// A bit per cell, 32 cells to a `u32`, for two-state Life-like rules on grids whose width is
+    /// a multiple of 32.
+    Packed,
+    /// A `u32` state per texel of a storage texture, which the display reads directly, for
+    /// bounded square grids.
+    Texture,
+}
+
+/// Which compute kernel advances unpacked cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kernel {
+    /// Each invocation reads its neighbours from the storage buffer.
+    Simple,
+    /// Each workgroup loads its cells and their neighbours into workgroup memory once, for
+    /// neighbourhoods of range 1.
+    Tiled,
+}
+
+impl Config {
+    /// Builds the configuration from command line arguments, e.g. `--rule B36/S23`.
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config> {
+        let mut config = Config {
+            width: 800,
+            height: 800,
+            grid_size: [32, 32],
+            rule: Rule::default(),
+            stencil: None,
+            topology: Topology::default(),
+            boundary: Boundary::default(),
+            engine: Engine::Gpu,
+            step: 1,
+            speed: 0.,
+            storage: Storage::Cells,
+            kernel: Kernel::Simple,
+            active_tiles: false,
+            benchmark: None,
+            headless: None,
+            output: None,
+            fallback_adapter: false,
+            soup: Soup::random(),
+            search: None,
+            census: None,
+            overlay: false,
+            detect: false,
+        };
+        let mut topology = None;
+        let mut speed = None;
+        let mut boundary = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--rule" => config.rule = value()?.parse()?,
+                "--stencil" => config.stencil = Some(value()?.parse()?),
+                "--topology" => topology = Some(value()?.parse()?),
+                "--grid-size" => config.grid_size = parse_grid_size(&value()?)?,
+                "--engine" => {
+                    config.engine = match value()?.to_ascii_lowercase().as_str() {
+                        "gpu" => Engine::Gpu,
+                        "hashlife" => Engine::HashLife,
+                        "cpu" => Engine::Cpu,
+                        engine => {
+                            bail!("unknown engine {engine:?}, expected gpu, hashlife or cpu")
+                        }
+                    }
+                }
+                "--step" => config.step = parse_step(&value()?)?,
+                "--speed" => speed = Some(parse_speed(&value()?)?),
+                "--storage" => {
+                    config.storage = match value()?.to_ascii_lowercase().as_str() {
+                        "cells" => Storage::Cells,
+                        "packed" => Storage::Packed,
+                        "texture" => Storage::Texture,
+                        storage => {
+                            bail!("unknown storage {storage:?}, expected cells, packed or texture")
+                        }
+                    }
+                }
+                "--kernel" => {
+                    config.kernel = match value()?.to_ascii_lowercase().as_str() {
+                        "simple" => Kernel::Simple,
+                        "tiled" => Kernel::Tiled,
+                        kernel => bail!("unknown kernel {kernel:?}, expected simple or tiled"),
+                    }
+                }
+                "--active-tiles" => config.active_tiles = true,
+                "--benchmark" => config.benchmark = Some(parse_step(&value()?)?),
+                "--headless" => config.headless = Some(parse_step(&value()?)?),
+                "--output" => config.output = Some(value()?.into()),
+                "--fallback-adapter" => config.fallback_adapter = true,
+                "--seed" => {
+                    let seed = value()?;
+                    config.soup.seed =
+                        (seed.parse()).with_context(|| format!("invalid seed {seed:?}"))?;
+                }
+                "--density" => {
+                    let density = value()?;
+                    config.soup.density = (density.parse())
+                        .with_context(|| format!("invalid density {density:?}"))?;
+                }
+                "--soup-size" => config.soup.size = Some(parse_grid_size(&value()?)?),
+                "--soup-at" => config.soup.corner = Some(parse_position(&value()?)?),
+                "--symmetry" => config.soup.symmetry = value()?.parse()?,
+                "--search" => config.search = Some(parse_step(&value()?)?),
+                "--census" => config.census = Some(value()?.into()),
+                "--overlay" => config.overlay = true,
+                "--detect" => config.detect = true,
+                "--boundary" => {
+                    let (parsed, size) = Boundary::parse(&value()?)?;
+                    boundary = Some(parsed);
+                    if let Some(size) = size {
+                        config.grid_size = size;
+                    }
+                }
+                _ => bail!("unknown argument {arg}"),
+            }
+        }
+
+        ensure!(
+            config.output.is_none() || config.headless.is_some(),
+            "--output writes the last frame of a --headless run"
+        );
+
+        // Ten steps a second unless told otherwise.
+        config.speed = speed.unwrap_or(10. * config.step as f64);
+
+        // HashLife's universe has no edges, and neither has a soup search's.
+        config.boundary = boundary.unwrap_or(match config.engine {
+            _ if config.search.is_some() => Boundary::Unbounded,
+            Engine::Gpu | Engine::Cpu => Boundary::default(),
+            Engine::HashLife => Boundary::Unbounded,
+        });
+        ensure!(
+            config.engine != Engine::HashLife || config.boundary == Boundary::Unbounded,
+            "HashLife only runs an unbounded universe"
+        );
+        ensure!(
+            config.engine != Engine::Cpu || config.boundary != Boundary::Unbounded,
+            "the CPU engine only runs a bounded grid"
+        );
+        ensure!(
+            !config.detect || config.engine == Engine::Gpu,
+            "--detect fingerprints the generations the compute shader runs"
+        );
+        if config.search.is_some() {
+            ensure!(
+                config.engine == Engine::Gpu && config.boundary == Boundary::Unbounded,
+                "soup searches run on the GPU in an unbounded universe"
+            );
+            // apgsearch's soups are 16x16.
+            config.soup.size.get_or_insert([16, 16]);
+            // Soups are watched for settling through the fingerprints of their generations.
+            config.detect = true;
+        }
+
+        if config.storage == Storage::Packed {
+            let [width, _] = config.grid_size;
+            ensure!(
+                width % 32 == 0,
+                "packed cells need a grid width that is a multiple of 32, got {width}"
+            );
+            ensure!(
+                matches!(
+                    config.boundary,
+                    Boundary::Torus { shift: [0, 0] } | Boundary::Plane
+                ),
+                "packed cells run on a plain torus or a plane"
+            );
+        }
+
+        if config.active_tiles {
+            ensure!(
+                config.boundary != Boundary::Unbounded
+                    && config.storage != Storage::Packed
+                    && config.kernel == Kernel::Simple,
+                "active tiles need a bounded grid, unpacked cells and the simple kernel"
+            );
+        }
+
+        // Hexagonal rules such as B2/S34H run on the hexagonal lattice unless told otherwise.
+        config.topology = topology.unwrap_or(match config.rule.neighbourhood {
+            Neighbourhood::Hexagonal => Topology::Hexagonal,
+            _ => Topology::Square,
+        });
+        if config.topology != Topology::Square {
+            let [width, height] = config.grid_size;
+            ensure!(
+                config.topology.wraps(config.grid_size),
+                "a {width}x{height} grid does not wrap around: the hexagonal lattice needs an even \
+                 height and the triangular lattices an even width and height"
+            );
+            ensure!(
+                config.boundary.preserves_lattice(),
+                "the hexagonal and triangular lattices need a plane or a torus shifted by an even number of cells"
+            );
+        }
+
+        if config.storage == Storage::Texture {
+            let [width, height] = config.grid_size;
+            let size = render::MAX_TEXTURE_SIZE;
+            ensure!(
+                width.max(height) <= size,
+                "a texture holds at most {size}x{size} cells, got {width}x{height}"
+            );
+            ensure!(
+                config.boundary != Boundary::Unbounded && config.topology == Topology::Square,
+                "texture storage needs a bounded square grid"
+            );
+        }
+
+        config.soup.check(config.grid_size)?;
+        config.check_rule(&config.rule)?;
+        Ok(config)
+    }
+
+    /// Checks that `rule` can run on the configured grid.
+    fn check_rule(&self, rule: &Rule) -> Result<()> {
+        let radius = match &self.stencil {
+            Some(stencil) => stencil.radius(),
+            None => rule.radius,
+        };
+        let [width, height] = self.grid_size;
+        ensure!(
+            width > 2 * radius && height > 2 * radius,
+            "a {width}x{height} grid is too small for the radius {radius} neighbourhood of {rule}"
+        );
+        ensure!(
+            self.boundary != Boundary::Sphere || width == height,
+            "a sphere needs a square grid, got {width}x{height}"
+        );
+        if self.boundary == Boundary::Unbounded {
+            ensure!(
+                self.topology == Topology::Square,
+                "an unbounded universe runs on the square grid"
+            );
+            ensure!(
+                !rule.births_from_nothing(),
+                "{rule} brings empty space to life, which would fill an unbounded universe"
+            );
+        }
+        if self.storage == Storage::Packed {
+            ensure!(
+                rule.is_life_like()
+                    && rule.states == 2
+                    && rule.neighbourhood == Neighbourhood::Moore
+                    && rule.isotropic.is_none()
+                    && rule.tree.is_none()
+                    && self.stencil.is_none()
+                    && self.topology == Topology::Square,
+                "packed cells only run two-state Life-like rules on the square grid, not {rule}"
+            );
+        }
+        if self.kernel == Kernel::Tiled {
+            ensure!(
+                self.storage != Storage::Packed
+                    && self.stencil.is_none()
+                    && rule.radius == 1
+                    && matches!(self.topology, Topology::Square | Topology::Hexagonal),
+                "the tiled kernel only runs range 1 neighbourhoods on the square or hexagonal grid, not {rule}"
+            );
+        }
+        if self.engine == Engine::HashLife {
+            ensure!(
+                HashLife::supports(rule) && self.stencil.is_none(),
+                "HashLife only runs two-state rules over the cells next to each cell, not {rule}"
+            );
+        }
+        if self.search.is_some() {
+            ensure!(
+                rule.states == 2 && rule.tree.is_none() && self.stencil.is_none(),
+                "soup searches run two-state rules over their own neighbourhoods, not {rule}"
+            );
+        }
+        if self.overlay {
+            ensure!(
+                rule.states == 2 && rule.tree.is_none() && self.topology == Topology::Square,
+                "the overlay labels objects of two-state rules on the square grid, not {rule}"
+            );
+        }
+        if rule.tree.is_some() {
+            ensure!(
+                self.stencil.is_none() && self.topology == Topology::Square,
+                "{rule} runs on the square grid with its own neighbourhood"
+            );
+        }
+        if rule.isotropic.is_some() {
+            ensure!(
+                self.stencil.is_none(),
+                "{rule} looks at which of the eight cells next to each cell are alive, not at a stencil"
+            );
+        }
+        match self.topology {
+            Topology::Square => {}
+            Topology::Hexagonal => ensure!(
+                self.stencil.is_none() && rule.radius == 1 && rule.isotropic.is_none(),
+                "the hexagonal lattice only runs totalistic rules over the six adjacent cells, not {rule}"
+            ),
+            Topology::TriangularVertex | Topology::TriangularEdge => ensure!(
+                self.stencil.is_none()
+                    && rule.radius == 1
+                    && rule.isotropic.is_none()
+                    && rule.neighbourhood == Neighbourhood::Moore,
+                "the triangular lattice only runs totalistic rules over the adjacent triangles, not {rule}"
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Parses `64` for a square grid or `64x48` for a rectangular one.
+fn parse_grid_size(size: &str) -> Result<[u32; 2]> {
+    let (width, height) = size.split_once(['x', 'X']).unwrap_or((size, size));
+    let parse = |dimension: &str| -> Result<u32> {
+        let dimension = dimension
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid grid size {size:?}"))?;
+        ensure!(dimension > 0, "invalid grid size {size:?}");
+        Ok(dimension)
+    };
+    Ok([parse(width)?, parse(height)?])
+}
+
+/// Parses the position of a cell, e.g. `24,16`.
+fn parse_position(position: &str) -> Result<[u32; 2]> {
+    let invalid = || format!("invalid position {position:?}, expected x,y");
+    let (x, y) = position.split_once(',').with_context(invalid)?;
+    Ok([
+        x.trim().parse().with_context(invalid)?,
+        y.trim().parse().with_context(invalid)?,
+    ])
+}
+
+/// Parses a speed in generations per second.
+fn parse_speed(speed: &str) -> Result<f64> {
+    let generations: f64 =
+        (speed.trim().parse()).with_context(|| format!("invalid speed {speed:?}"))?;
+    let (slowest, fastest) = (clock::SPEEDS.start(), clock::SPEEDS.end());
+    ensure!(
+        clock::SPEEDS.contains(&generations),
+        "the speed must be between {slowest} and {fastest} generations per second, got {speed}"
+    );
+    Ok(generations)
+}
+
+/// Parses a number of generations, either written out or as a power of two such as `2^40`.
+fn parse_step(step: &str) -> Result<u64> {
+    let invalid = || format!("invalid step {step:?}");
+    let generations = match step.trim().split_once('^') {
+        Some(("2", exponent)) => {
+            let exponent: u32 = exponent.parse().with_context(invalid)?;
+            1u64.checked_shl(exponent).with_context(invalid)?
+        }
+        Some(_) => bail!(invalid()),
+        None => step.trim().parse().with_context(invalid)?,
+    };
+    ensure!(generations > 0, invalid());
+    ensure!(
+        generations <= MAX_GENERATION,
+        "invalid step {step:?}: at most 2^{} generations at a time",
+        MAX_GENERATION.ilog2()
+    );
+    Ok(generations)
 }
 
 #[pollster::main]
 async fn main() -> Result<()> {
+    let config = Config::from_args(std::env::args().skip(1))?;
+    // Runs worth seeing again can be replayed with the same arguments and this seed.
+    println!("seed {}", config.soup.seed);
+    if let Some(generations) = config.benchmark {
+        return benchmark(config, generations).await;
+    }
+    if let Some(generations) = config.headless {
+        return headless(config, generations).await;
+    }
+    if let Some(soups) = config.search {
+        return search::search(&config, soups).await;
+    }
 
-    let config = Config{width:800,height:800,grid_size:32};
-
-    let event_loop = EventLoop::new()?;
+    let event_loop = EventLoopBuilder::<Rule>::with_user_event().build()?;
     let window_size = winit::dpi::PhysicalSize::new(config.width, config.height);
 
     let window = WindowBuilder::new()
@@ -29,13 +502,67 @@ async fn main() -> Result<()> {
         .build(&event_loop)?;
 
     let (device, queue, surface) = connect_to_gpu(&window).await?;
-    let mut renderer = render::Renderer::new(device, queue,&config);
+    let mut renderer = Renderer::new(device, queue, &config);
+    let mut engine = cpu_engine(&config, &renderer);
+    let mut overlay = config.overlay.then(|| Overlay::new(&config));
 
+    // Rulestrings typed into the terminal replace the running rule.
+    let proxy = event_loop.create_proxy();
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse::<Rule>() {
+                Ok(rule) => {
+                    if proxy.send_event(rule).is_err() {
+                        break;
+                    }
+                }
+                Err(error) => eprintln!("{error}"),
+            }
+        }
+    });
+
+    let mut clock = Clock::new(config.speed, config.step, Instant::now());
     event_loop.run(|event, control_handle| {
-        control_handle.set_control_flow(ControlFlow::Poll);
         match event {
+            Event::UserEvent(rule) => match config.check_rule(&rule) {
+                Ok(()) => {
+                    println!("switching to {rule}");
+                    match &mut engine {
+                        Some(engine) => engine.set_rule(&rule),
+                        None => renderer.set_rule(&rule),
+                    }
+                    if let Some(overlay) = &mut overlay {
+                        overlay.set_rule(&rule);
+                    }
+                }
+                Err(error) => eprintln!("{error}"),
+            },
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => control_handle.exit(),
+                // The arrow keys move the view of an unbounded universe by an eighth of its size.
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Named(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    let [width, height] = config.grid_size.map(|size| (size as i32 / 8).max(1));
+                    match key {
+                        NamedKey::ArrowLeft => renderer.pan([-width, 0]),
+                        NamedKey::ArrowRight => renderer.pan([width, 0]),
+                        NamedKey::ArrowUp => renderer.pan([0, height]),
+                        NamedKey::ArrowDown => renderer.pan([0, -height]),
+                        _ => return,
+                    }
+                    window.request_redraw();
+                }
                 WindowEvent::RedrawRequested => {
                     let frame = surface
                         .get_current_texture()
@@ -43,14 +570,53 @@ async fn main() -> Result<()> {
                     let render_target = frame
                         .texture
                         .create_view(&wgpu::TextureViewDescriptor::default());
-                    renderer.render_frame(&render_target);
+                    let due = clock.due(Instant::now());
+                    let started = Instant::now();
+                    advance(
+                        &mut renderer,
+                        &mut engine,
+                        &mut overlay,
+                        due,
+                        &render_target,
+                    );
+                    clock.ran(due, started.elapsed());
+                    for (generation, event) in renderer.events() {
+                        println!("generation {generation}: {event}");
+                    }
+                    let mut title = Vec::new();
+                    if let Some(engine) = &engine {
+                        title.push(format!(
+                            "generation {}, population {}",
+                            engine.generation(),
+                            engine.population()
+                        ));
+                    }
+                    if let Some(overlay) = &overlay {
+                        title.push(overlay.summary());
+                    }
+                    if let Some(event) = renderer.detector().and_then(Detector::state) {
+                        title.push(event.to_string());
+                    }
+                    if renderer.outgrown() {
+                        title.push(OUTGROWN.to_string());
+                    }
+                    if !title.is_empty() {
+                        window.set_title(&title.join("; "));
+                    }
                     frame.present();
-
-                    thread::sleep(Duration::from_millis(100));
-                    window.request_redraw();
                 }
                 _ => (),
             },
+            // Draw again when the next step is due, waiting for it without blocking the events
+            // in between. Speeds beyond the frame rate draw every frame, running all the
+            // generations due since the last one.
+            Event::AboutToWait => {
+                let next = clock.next_due();
+                if next <= Instant::now() {
+                    window.request_redraw();
+                }
+                control_handle.set_control_flow(ControlFlow::WaitUntil(next));
+            }
             _ => (),
         }
     })?;
@@ -58,8 +624,130 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// The engine running on the CPU, if any, starting from the soup the renderer was set up with.
+fn cpu_engine(config: &Config, renderer: &Renderer) -> Option<Box<dyn Simulation>> {
+    let soup = renderer.region([0, 0], config.grid_size);
+    match config.engine {
+        Engine::Gpu => None,
+        Engine::HashLife => Some(Box::new(HashLife::new(
+            &config.rule,
+            [0, 0],
+            config.grid_size,
+            &soup,
+        ))),
+        Engine::Cpu => Some(Box::new(Cpu::new(config, soup))),
+    }
+}
+
+/// Runs `generations` more generations, on the CPU engine if there is one, and draws the cells
+/// in view to `target`, with the overlay's boxes and labels if there is one.
+fn advance(
+    renderer: &mut Renderer,
+    engine: &mut Option<Box<dyn Simulation>>,
+    overlay: &mut Option<Overlay>,
+    generations: u64,
+    target: &wgpu::TextureView,
+) {
+    let [x, y, width, height] = renderer.view();
+    let origin = [x, y].map(i64::from);
+    let size = [width as u32, height as u32];
+    match engine {
+        Some(engine) => {
+            engine.step(generations);
+            let cells = engine.region(origin, size);
+            if let Some(overlay) = overlay {
+                renderer.set_overlay(&overlay.update(&cells, size));
+            }
+            renderer.show(target, &cells);
+        }
+        None => {
+            renderer.step(generations);
+            if let Some(overlay) = overlay {
+                let cells = renderer.region(origin, size);
+                renderer.set_overlay(&overlay.update(&cells, size));
+            }
+            renderer.render_frame(target);
+        }
+    }
+}
+
+/// Runs `generations` generations without a window or a surface, drawing the last one into an
+/// offscreen texture, then prints the population and writes the frame to the output file if
+/// there is one.
+async fn headless(config: Config, generations: u64) -> Result<()> {
+    let (device, queue) = render::connect_headless(config.fallback_adapter).await?;
+    let mut renderer = Renderer::new(device, queue, &config);
+    let mut engine = cpu_engine(&config, &renderer);
+    let mut overlay = config.overlay.then(|| Overlay::new(&config));
+    let size = [config.width, config.height];
+    let target = renderer.offscreen_target(size);
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let start = Instant::now();
+    advance(&mut renderer, &mut engine, &mut overlay, generations, &view);
+    for (generation, event) in renderer.events() {
+        println!("generation {generation}: {event}");
+    }
+    let simulation: &dyn Simulation = match &engine {
+        Some(engine) => engine.as_ref(),
+        None => &renderer,
+    };
+    println!(
+        "generation {}, population {} after {:.2?}",
+        simulation.generation(),
+        simulation.population(),
+        start.elapsed()
+    );
+    if let Some(overlay) = &overlay {
+        println!("{}", overlay.summary());
+    }
+    if renderer.outgrown() {
+        println!("{OUTGROWN}");
+    }
+
+    if let Some(path) = &config.output {
+        write_ppm(path, size, &renderer.read_frame(&target))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes an image as a binary PPM.
+fn write_ppm(path: &Path, [width, height]: [u32; 2], pixels: &[[u8; 3]]) -> std::io::Result<()> {
+    let mut ppm = format!("P6\n{width} {height}\n255\n").into_bytes();
+    ppm.extend(pixels.iter().flatten());
+    std::fs::write(path, ppm)
+}
+
+/// Times `generations` generations of the soup under each kernel and prints the generations per
+/// second, without opening a window.
+async fn benchmark(mut config: Config, generations: u64) -> Result<()> {
+    config.kernel = Kernel::Tiled;
+    config.check_rule(&config.rule)?;
+    let [width, height] = config.grid_size;
+    println!("{} on a {width}x{height} grid", config.rule);
 
-async fn connect_to_gpu(window: &Window) -> Result<(wgpu::Device, wgpu::Queue, wgpu::Surface)> {
+    let mut rates = Vec::new();
+    for kernel in [Kernel::Simple, Kernel::Tiled] {
+        config.kernel = kernel;
+        let (device, queue) = render::connect_headless(config.fallback_adapter).await?;
+        let mut renderer = Renderer::new(device, queue, &config);
+        // Compile the pipelines and fill the caches before timing.
+        renderer.step(generations.min(16));
+        renderer.wait();
+
+        let start = Instant::now();
+        renderer.step(generations);
+        renderer.wait();
+        let rate = generations as f64 / start.elapsed().as_secs_f64();
+        println!("{kernel:?}: {rate:.1} generations/s");
+        rates.push(rate);
+    }
+    println!("speedup: {:.2}x", rates[1] / rates[0]);
+    Ok(())
+}
+
+async fn connect_to_gpu(window: &Window) -> Result<(wgpu::Device, wgpu::Queue, wgpu::Surface<'_>)> {
     use wgpu::TextureFormat::{Bgra8Unorm, Rgba8Unorm};
 
     // Create an "instance" of wgpu. This is the entry-point to the API
@@ -113,3 +801,33 @@ async fn connect_to_gpu(window: &Window) -> Result<(wgpu::Device, wgpu::Queue, w
 
     Ok((device, queue, surface))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    fn config(args: &[&str]) -> anyhow::Result<Config> {
+        Config::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn conflicting_arguments_are_rejected() {
+        assert!(config(&["--rule", "B2-a/S12"]).is_ok());
+        assert!(config(&["--stencil", "knight"]).is_ok());
+        assert!(config(&["--engine", "hashlife", "--headless", "2^56"]).is_ok());
+        for args in [
+            &["--rule", "B2-a/S12", "--stencil", "knight"][..],
+            &["--rule", "B3/S2-i34q", "--stencil", "moore"],
+            &["--rule", "WireWorld", "--stencil", "vonneumann"],
+            &["--engine", "hashlife", "--boundary", "T"],
+            &["--engine", "cpu", "--boundary", "P0,0"],
+            &["--output", "frame.ppm"],
+            &["--step", "0"],
+            &["--step", "2^57"],
+            &["--engine", "hashlife", "--headless", "2^62"],
+            &["--headless", "18446744073709551615"],
+        ] {
+            assert!(config(args).is_err(), "{args:?}");
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Objects made of live cells: the cells are split into objects, and each object is run on its own
+//! on the CPU to learn whether it is a still life, an oscillator or a spaceship.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{boundary::Boundary, cpu::Cpu, rule::Rule, simulation::Simulation, Config};
+
+/// Most shapes remembered before starting afresh.
+const MAX_KNOWN: usize = 1 << 16;
+
+/// How an object repeats.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Motion {
+    /// Generations before it comes back to its first phase.
+    pub period: u32,
+    /// How far it has moved by then.
+    pub displacement: [i32; 2],
+    /// Its phases from the first on, each moved to the origin.
+    pub phases: Vec<Vec<[i32; 2]>>,
+}
+
+impl Motion {
+    pub fn is_still_life(&self) -> bool {
+        self.period == 1 && self.displacement == [0, 0]
+    }
+
+    pub fn is_spaceship(&self) -> bool {
+        self.displacement != [0, 0]
+    }
+}
+
+/// Follows objects on their own for up to a number of generations, remembering how each shape
+/// behaved.
+pub struct Follower {
+    config: Config,
+    max_period: u32,
+    known: HashMap<Vec<[i32; 2]>, Option<Motion>>,
+}
+
+impl Follower {
+    /// Objects follow the configured rule; only two states, live and dead, are looked at.
+    pub fn new(config: &Config, max_period: u32) -> Follower {
+        Follower {
+            config: config.clone(),
+            max_period,
+            known: HashMap::new(),
+        }
+    }
+
+    /// Follows objects under `rule` from now on, forgetting how shapes behaved under the old one.
+    pub fn set_rule(&mut self, rule: &Rule) {
+        self.config.rule = rule.clone();
+        self.known.clear();
+    }
+
+    /// How an object repeats, or `None` if it dies or doesn't repeat within the longest period.
+    pub fn follow(&mut self, object: &[[i32; 2]]) -> Option<Motion> {
+        let (start, _) = normalise(object);
+        if let Some(motion) = self.known.get(&start) {
+            return motion.clone();
+        }
+        if self.known.len() >= MAX_KNOWN {
+            self.known.clear();
+        }
+        let motion = self.run(&start);
+        self.known.insert(start, motion.clone());
+        motion
+    }
+
+    fn run(&self, start: &[[i32; 2]]) -> Option<Motion> {
+        // Room for the object to move as fast as anything can for the longest period.
+        let reach = match &self.config.stencil {
+            Some(stencil) => stencil.radius(),
+            None => self.config.rule.radius,
+        };
+        let margin = (self.max_period * reach) as i32 + 2;
+        let size = [0, 1].map(|axis| {
+            let extent = start.iter().map(|cell| cell[axis]).max().unwrap_or(0) + 1;
+            (extent + 2 * margin) as u32
+        });
+        let config = Config {
+            grid_size: size,
+            boundary: Boundary::Plane,
+            ..self.config.clone()
+        };
+        let mut cells = vec![0; (size[0] * size[1]) as usize];
+        for &[x, y] in start {
+            cells[((y + margin) as u32 * size[0] + (x + margin) as u32) as usize] = 1;
+        }
+        let mut cpu = Cpu::new(&config, cells);
+
+        let mut phases = vec![start.to_vec()];
+        for period in 1..=self.max_period {
+            cpu.step(1);
+            let live: Vec<[i32; 2]> = (cpu.region([0, 0], size).iter().enumerate())
+                .filter(|&(_, &state)| state == 1)
+                .map(|(i, _)| [(i as u32 % size[0]) as i32, (i as u32 / size[0]) as i32])
+                .collect();
+            if live.is_empty() {
+                return None;
+            }
+            let (phase, moved) = normalise(&live);
+            if phase == start {
+                return Some(Motion {
+                    period,
+                    displacement: moved.map(|at| at - margin),
+                    phases,
+                });
+            }
+            phases.push(phase);
+        }
+        None
+    }
+
+    /// The objects the live cells make up, and how each repeats. Cells within two cells of each
+    /// other may affect each other, but they are only taken as one object if their separate
+    /// parts don't each repeat on their own.
+    pub fn identify(&mut self, cells: &[[i32; 2]]) -> Vec<(Vec<[i32; 2]>, Option<Motion>)> {
+        let mut objects = Vec::new();
+        for cluster in split(cells, 2) {
+            let whole = self.follow(&cluster);
+            let parts = split(&cluster, 1);
+            let motions: Option<Vec<Motion>> = parts.iter().map(|part| self.follow(part)).collect();
+            match motions {
+                Some(motions) if whole.is_some() && parts.len() > 1 => {
+                    objects.extend(parts.into_iter().zip(motions.into_iter().map(Some)))
+                }
+                _ => objects.push((cluster, whole)),
+            }
+        }
+        objects
+    }
+}
+
+/// Splits live cells into groups of cells at most `distance` cells apart.
+pub fn split(cells: &[[i32; 2]], distance: i32) -> Vec<Vec<[i32; 2]>> {
+    let mut left: HashSet<[i32; 2]> = cells.iter().copied().collect();
+    let mut objects = Vec::new();
+    for &cell in cells {
+        if !left.remove(&cell) {
+            continue;
+        }
+        let mut object = vec![cell];
+        let mut next = 0;
+        while let Some(&[x, y]) = object.get(next) {
+            for dy in -distance..=distance {
+                for dx in -distance..=distance {
+                    if left.remove(&[x + dx, y + dy]) {
+                        object.push([x + dx, y + dy]);
+                    }
+                }
+            }
+            next += 1;
+        }
+        object.sort();
+        objects.push(object);
+    }
+    objects
+}
+
+/// The cells moved so that their bounding box starts at 0, and where it started.
+pub fn normalise(cells: &[[i32; 2]]) -> (Vec<[i32; 2]>, [i32; 2]) {
+    let min = [0, 1].map(|axis| cells.iter().map(|cell| cell[axis]).min().unwrap_or(0));
+    let mut cells: Vec<_> = (cells.iter())
+        .map(|&[x, y]| [x - min[0], y - min[1]])
+        .collect();
+    cells.sort();
+    (cells, min)
+}
@@ -0,0 +1,245 @@
+//! The overlay of the live view: the objects in view are followed on their own on the CPU, and
+//! each gets a box coloured by what it is and a label: `S` for a still life, the period for an
+//! oscillator, e.g. `P2`, and the displacement per period for a spaceship, e.g. `(1,-1)/4`.
+
+use crate::{
+    objects::{split, Follower, Motion},
+    render::Rect,
+    rule::Rule,
+    Config,
+};
+
+/// Longest period looked for, enough for the pentadecathlon.
+const MAX_PERIOD: u32 = 16;
+
+/// Most cells of an object followed; larger clusters are still settling and go unlabelled.
+const MAX_CELLS: usize = 128;
+
+/// Screen pixels per pixel of the label font, and thickness of the boxes.
+const FONT_SCALE: f32 = 2.;
+
+const STILL_LIFE: [f32; 4] = [0.3, 0.9, 0.3, 1.];
+const OSCILLATOR: [f32; 4] = [1., 0.8, 0.1, 1.];
+const SPACESHIP: [f32; 4] = [1., 0.3, 0.5, 1.];
+
+pub struct Overlay {
+    follower: Follower,
+    /// Width of a screen pixel in cells.
+    pixel: f32,
+    /// Number of still lifes, oscillators and spaceships in view.
+    counts: [usize; 3],
+}
+
+impl Overlay {
+    pub fn new(config: &Config) -> Overlay {
+        // The longer side of the view fills the window.
+        let [width, height] = config.grid_size;
+        let pixels = match width >= height {
+            true => config.width,
+            false => config.height,
+        };
+        Overlay {
+            follower: Follower::new(config, MAX_PERIOD),
+            pixel: width.max(height) as f32 / pixels as f32,
+            counts: [0; 3],
+        }
+    }
+
+    /// Labels objects by how they behave under `rule` from now on.
+    pub fn set_rule(&mut self, rule: &Rule) {
+        self.follower.set_rule(rule);
+    }
+
+    /// Finds the objects among `cells`, the states of the `[width, height]` cells in view row by
+    /// row from the bottom, and returns the boxes and labels to draw over them.
+    pub fn update(&mut self, cells: &[u32], [width, _]: [u32; 2]) -> Vec<Rect> {
+        let live: Vec<[i32; 2]> = (cells.iter().enumerate())
+            .filter(|&(_, &state)| state == 1)
+            .map(|(i, _)| [(i as u32 % width) as i32, (i as u32 / width) as i32])
+            .collect();
+        let settled: Vec<[i32; 2]> = (split(&live, 2).into_iter())
+            .filter(|cluster| cluster.len() <= MAX_CELLS)
+            .flatten()
+            .collect();
+
+        self.counts = [0; 3];
+        let mut rects = Vec::new();
+        for (object, motion) in self.follower.identify(&settled) {
+            let Some(motion) = motion else {
+                continue;
+            };
+            let kind = match &motion {
+                _ if motion.is_still_life() => 0,
+                _ if motion.is_spaceship() => 2,
+                _ => 1,
+            };
+            self.counts[kind] += 1;
+            let colour = [STILL_LIFE, OSCILLATOR, SPACESHIP][kind];
+            let [x0, y0] = [0, 1].map(|axis| object.iter().map(|cell| cell[axis]).min().unwrap());
+            let [x1, y1] = [0, 1].map(|axis| object.iter().map(|cell| cell[axis]).max().unwrap());
+            let [x0, y0, x1, y1] = [x0, y0, x1 + 1, y1 + 1].map(|at| at as f32);
+            let t = FONT_SCALE * self.pixel;
+            for corners in [
+                [x0 - t, y0 - t, x1 + t, y0],
+                [x0 - t, y1, x1 + t, y1 + t],
+                [x0 - t, y0, x0, y1],
+                [x1, y0, x1 + t, y1],
+            ] {
+                rects.push(Rect { corners, colour });
+            }
+            self.text(&mut rects, &label(&motion), [x0 - t, y1 + 2. * t], colour);
+        }
+        rects
+    }
+
+    /// Writes `text` with its bottom left corner at `[x, y]`.
+    fn text(&self, rects: &mut Vec<Rect>, text: &str, [x, y]: [f32; 2], colour: [f32; 4]) {
+        let size = FONT_SCALE * self.pixel;
+        for (i, character) in text.chars().enumerate() {
+            for (row, bits) in glyph(character).into_iter().enumerate() {
+                for column in (0..3).filter(|column| bits & (4 >> column) != 0) {
+                    let left = x + (4 * i + column) as f32 * size;
+                    let bottom = y + (4 - row) as f32 * size;
+                    rects.push(Rect {
+                        corners: [left, bottom, left + size, bottom + size],
+                        colour,
+                    });
+                }
+            }
+        }
+    }
+
+    /// What was found in view, e.g. `3 still lifes, 1 oscillator, 0 spaceships`.
+    pub fn summary(&self) -> String {
+        let [still_lifes, oscillators, spaceships] = self.counts;
+        let count = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+        format!(
+            "{}, {}, {}",
+            count(still_lifes, "still life"),
+            count(oscillators, "oscillator"),
+            count(spaceships, "spaceship")
+        )
+    }
+}
+
+/// The label of an object.
+fn label(motion: &Motion) -> String {
+    let [dx, dy] = motion.displacement;
+    match motion {
+        _ if motion.is_still_life() => "S".into(),
+        _ if motion.is_spaceship() => format!("({dx},{dy})/{}", motion.period),
+        _ => format!("P{}", motion.period),
+    }
+}
+
+/// The five rows of a character of the label font from the top, the left column the highest of
+/// three bits.
+fn glyph(character: char) -> [u8; 5] {
+    match character {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'P' => [0b111, 0b101, 0b111, 0b100, 0b100],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0; 5],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glyph, label, Overlay, OSCILLATOR, SPACESHIP, STILL_LIFE};
+    use crate::{objects::Follower, Config};
+
+    #[test]
+    fn objects_in_view_are_boxed_and_labelled() {
+        let args = ["--grid-size", "48", "--overlay"];
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        // A block, a blinker, a glider, and a cluster too big to follow.
+        let mut cells = vec![0; 48 * 48];
+        let mut set = |rows: &[&str], [x, y]: [usize; 2]| {
+            for (row, line) in rows.iter().enumerate() {
+                for (column, cell) in line.chars().enumerate() {
+                    if cell == 'O' {
+                        cells[(y - row) * 48 + x + column] = 1;
+                    }
+                }
+            }
+        };
+        set(&["OO", "OO"], [4, 40]);
+        set(&["OOO"], [20, 40]);
+        set(&[".O.", "..O", "OOO"], [4, 10]);
+        set(&["OOOOOOOOOOOOOOOOOOOO"; 8], [24, 20]);
+
+        let mut overlay = Overlay::new(&config);
+        let rects = overlay.update(&cells, [48, 48]);
+        assert_eq!(overlay.summary(), "1 still life, 1 oscillator, 1 spaceship");
+        // Under a rule where every cell survives and none is born, everything is a still life.
+        overlay.set_rule(&"B/S012345678".parse().unwrap());
+        overlay.update(&cells, [48, 48]);
+        assert_eq!(
+            overlay.summary(),
+            "3 still lifes, 0 oscillators, 0 spaceships"
+        );
+        overlay.set_rule(&"B3/S23".parse().unwrap());
+        overlay.update(&cells, [48, 48]);
+        assert_eq!(overlay.summary(), "1 still life, 1 oscillator, 1 spaceship");
+        // The block's box is drawn just outside its cells.
+        let t = 2. * 48. / 800.;
+        assert!(rects.iter().any(
+            |rect| rect.colour == STILL_LIFE && rect.corners == [4. - t, 39. - t, 6. + t, 39.]
+        ));
+        for (colour, text) in [
+            (STILL_LIFE, "S"),
+            (OSCILLATOR, "P2"),
+            (SPACESHIP, "(1,-1)/4"),
+        ] {
+            let pixels: usize = (text.chars())
+                .flat_map(glyph)
+                .map(|row| row.count_ones() as usize)
+                .sum();
+            let drawn = rects.iter().filter(|rect| rect.colour == colour).count();
+            assert_eq!(drawn, 4 + pixels, "{text}");
+        }
+    }
+
+    #[test]
+    fn labels_say_how_objects_move() {
+        let config = Config::from_args([String::from("--overlay")].into_iter()).unwrap();
+        let mut follower = Follower::new(&config, super::MAX_PERIOD);
+        for (cells, text) in [
+            (vec![[0, 0], [1, 0], [0, 1], [1, 1]], "S"),
+            (vec![[0, 0], [0, 1], [0, 2]], "P2"),
+            // A lightweight spaceship flying left.
+            (
+                vec![
+                    [1, 3],
+                    [4, 3],
+                    [0, 2],
+                    [0, 1],
+                    [4, 1],
+                    [0, 0],
+                    [1, 0],
+                    [2, 0],
+                    [3, 0],
+                ],
+                "(-2,0)/4",
+            ),
+        ] {
+            let motion = follower.follow(&cells).unwrap();
+            assert_eq!(label(&motion), text);
+            assert!(text.chars().all(|character| glyph(character) != [0; 5]));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Plain reimplementations of the boundaries and of two-state rules, which the GPU's storage
+//! layouts, kernels and boundaries are all tested against.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{boundary::Boundary, render::Renderer, rule::Rule, simulation::Simulation, Config};
+
+/// Each boundary, with and without a shift or a twist.
+pub const BOUNDARIES: [&str; 8] = ["T", "T+3,", "T,-2", "P", "K*,", "K,*", "C", "S"];
+
+/// The cell that a position outside a `[width, height]` grid stands for, if any.
+fn expected_cell(
+    boundary: Boundary,
+    [width, height]: [i32; 2],
+    [x, y]: [i32; 2],
+) -> Option<[i32; 2]> {
+    let inside = |[x, y]: [i32; 2]| (0..width).contains(&x) && (0..height).contains(&y);
+    let wrapped = [x.rem_euclid(width), y.rem_euclid(height)];
+    let crossings = [x.div_euclid(width), y.div_euclid(height)];
+    match boundary {
+        Boundary::Torus { shift } => Some([
+            (x + crossings[1] * shift[0]).rem_euclid(width),
+            (y + crossings[0] * shift[1]).rem_euclid(height),
+        ]),
+        Boundary::Plane => inside([x, y]).then_some([x, y]),
+        Boundary::KleinBottle { twisted_sides } => {
+            let [mut x, mut y] = wrapped;
+            if !twisted_sides && crossings[1] % 2 != 0 {
+                x = width - 1 - x;
+            }
+            if twisted_sides && crossings[0] % 2 != 0 {
+                y = height - 1 - y;
+            }
+            Some([x, y])
+        }
+        Boundary::CrossSurface => {
+            let [mut x, mut y] = wrapped;
+            if crossings[1] % 2 != 0 {
+                x = width - 1 - x;
+            }
+            if crossings[0] % 2 != 0 {
+                y = height - 1 - y;
+            }
+            Some([x, y])
+        }
+        Boundary::Sphere => {
+            // Rows above the top edge are columns right of the left edge, read from the top
+            // left corner down; rows below the bottom edge are columns left of the right edge.
+            let size = width;
+            let mut position = [x, y];
+            for _ in 0..2 {
+                let [x, y] = position;
+                position = if y >= size {
+                    [y - size, size - 1 - x]
+                } else if x < 0 {
+                    [size - 1 - y, size + x]
+                } else if y < 0 {
+                    [size + y, size - 1 - x]
+                } else if x >= size {
+                    [size - 1 - y, x - size]
+                } else {
+                    [x, y]
+                };
+            }
+            inside(position).then_some(position)
+        }
+        Boundary::Unbounded => unreachable!("an unbounded universe has no grid"),
+    }
+}
+
+/// One generation of a two-state rule over the Moore neighbourhood of its radius.
+pub fn expected_step(boundary: Boundary, size: [i32; 2], rule: &Rule, cells: &[u32]) -> Vec<u32> {
+    let r = rule.radius as i32;
+    let [width, height] = size;
+    let state = |position| {
+        expected_cell(boundary, size, position).map_or(0, |[x, y]| cells[(y * width + x) as usize])
+    };
+    let mut next = vec![0; cells.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut count = 0;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    if (dx, dy) != (0, 0) || rule.include_centre {
+                        count += state([x + dx, y + dy]);
+                    }
+                }
+            }
+            let alive = state([x, y]) == 1;
+            let counts = if alive { &rule.survival } else { &rule.birth };
+            next[(y * width + x) as usize] = u32::from(counts.contains(count));
+        }
+    }
+    next
+}
+
+/// Runs a few generations of `rule` on a `spec` grid on the GPU, with more command line arguments
+/// in `extra`, and checks each against [`expected_step`].
+pub fn check(spec: &str, [width, height]: [u32; 2], rule: &str, extra: &[&str]) {
+    let size = format!("{width}x{height}");
+    let args = ["--boundary", spec, "--grid-size", &size, "--rule", rule];
+    let args = args.iter().chain(extra).map(|arg| arg.to_string());
+    let config = Config::from_args(args).unwrap();
+    let Some(mut renderer) = Renderer::headless(&config) else {
+        return;
+    };
+    let mut rng = StdRng::seed_from_u64(u64::from(width * height));
+    let mut cells: Vec<u32> = (0..width * height)
+        .map(|_| u32::from(rng.gen_bool(0.4)))
+        .collect();
+    renderer.set_cells(&cells);
+    for generation in 1..=4 {
+        renderer.step(1);
+        let size = config.grid_size.map(|size| size as i32);
+        cells = expected_step(config.boundary, size, &config.rule, &cells);
+        assert_eq!(
+            renderer.cells(),
+            cells,
+            "generation {generation} of {rule} on {spec} with size {width}x{height} {extra:?}"
+        );
+    }
+}
@@ -1,25 +1,157 @@
+use anyhow::{Context, Result};
 use bytemuck::{Pod, Zeroable};
 use wgpu::PipelineCompilationOptions;
 
-use crate::Config;
-extern crate rand;
-use rand::Rng;
+use crate::{
+    boundary::Boundary,
+    chunks::{ChunkEntry, Chunks, CHUNK_SIZE},
+    fingerprint::{Detector, Event, Fingerprint, FINGERPRINT_WORDS},
+    rule::{Neighbourhood, Rule},
+    simulation::Simulation,
+    topology::Topology,
+    Config, Kernel, Storage,
+};
 
 pub struct Renderer {
     device: wgpu::Device,
     queue: wgpu::Queue,
-    grid_size: u32,
+    grid_size: [u32; 2],
+    vertices_per_cell: u32,
+    uniforms: Uniforms,
+    bindgroup_layout: wgpu::BindGroupLayout,
+    shared_buffers: SharedBuffers,
+    storage_buffers: [wgpu::Buffer; 2],
+    /// The ping-pong cell textures of texture storage, 1x1 placeholders otherwise.
+    cell_textures: [wgpu::Texture; 2],
     render_pipeline: wgpu::RenderPipeline,
+    /// Draws the rectangles of the overlay over the cells.
+    overlay_pipeline: wgpu::RenderPipeline,
+    /// The rectangles of the overlay, and how many of them there are.
+    overlay: (wgpu::Buffer, u32),
+    /// The kernel chosen for the configured storage and kernel.
     compute_pipeline: wgpu::ComputePipeline,
+    prefix_pipeline: wgpu::ComputePipeline,
     display_bindgroups: [wgpu::BindGroup; 2],
     frame_count: usize,
     workgroup_count: (u32, u32, u32),
+    /// The chunks of an unbounded universe, `None` on a bounded grid.
+    chunks: Option<Chunks>,
+    /// Number of chunks the cell buffers have room for.
+    capacity: usize,
+    /// Whether chunks have been left out for want of room.
+    outgrown: bool,
+    /// Where the kernel's chunk activity is read back after each generation.
+    activity_readback: wgpu::Buffer,
+    /// The passes listing the tiles to compute, when only active tiles are.
+    active_tiles: Option<ActiveTiles>,
+    /// The passes fingerprinting each generation, when generations are tracked.
+    tracking: Option<Tracking>,
+}
+
+/// What fingerprinting each generation and watching the fingerprints takes.
+struct Tracking {
+    bounds_pipeline: wgpu::ComputePipeline,
+    cells_pipeline: wgpu::ComputePipeline,
+    /// The fingerprints of a batch of generations, copied out of the activity buffer.
+    history: wgpu::Buffer,
+    history_readback: wgpu::Buffer,
+    detector: Detector,
+    /// The events not handed out yet, and the generations they happened in.
+    events: Vec<(u64, Event)>,
+}
+
+/// What computing only the tiles near last generation's changes takes on top of the kernel.
+struct ActiveTiles {
+    /// Number of tiles the grid is cut into.
+    tiles: u32,
+    mark_pipeline: wgpu::ComputePipeline,
+    dispatch_pipeline: wgpu::ComputePipeline,
+    /// The workgroups the kernel is dispatched over, copied out of the activity buffer.
+    dispatch_args: wgpu::Buffer,
 }
 
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
 struct Uniforms {
     grid_size: [f32; 2],
+    states: u32,
+    radius: u32,
+    neighbourhood: u32,
+    include_centre: u32,
+    stencil_len: u32,
+    isotropic: u32,
+    birth: [u32; 16],
+    survival: [u32; 16],
+    topology: u32,
+    tree_neighbours: u32,
+    tree_root: u32,
+    boundary: u32,
+    shift: [i32; 2],
+    /// How far from a live cell the next generation can change; at least this close to the edge
+    /// of a chunk, live cells need the chunk next door.
+    reach: u32,
+    /// How the cells are stored: 0 a state per word, 1 packed 32 to a word, 2 in textures.
+    cell_storage: u32,
+    /// The cells shown in an unbounded universe: bottom left corner, width and height.
+    view: [i32; 4],
+    /// 1 if only the tiles within reach of last generation's changes are computed.
+    active_tiles: u32,
+    /// Word of the activity buffer where the fingerprint of a generation is gathered, after
+    /// the words of the chunks or tiles.
+    fingerprint: u32,
+    _padding: [u32; 2],
+}
+
+/// A rectangle drawn over the cells, in cells from the bottom left corner of the view.
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct Rect {
+    /// Bottom left and top right corners.
+    pub corners: [f32; 4],
+    pub colour: [f32; 4],
+}
+
+/// Largest width or height of a grid in texture storage, wgpu's default limit on texture size.
+pub const MAX_TEXTURE_SIZE: u32 = 8192;
+
+/// Format of the frames drawn, on a window's surface or offscreen.
+const FRAME_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+
+/// Width and height of the tiles of active tile tracking, `TILE_SIZE` in the kernel.
+const TILE_SIZE: u32 = 8;
+
+/// Words of the activity buffer ahead of the tile flags with active tiles: the number of active
+/// tiles and the workgroups to dispatch for them.
+const ACTIVE_HEADER: u32 = 4;
+
+/// Most generations recorded into one command buffer.
+const MAX_BATCH: u64 = 256;
+
+/// Size of a fingerprint as the compute shader gathers it.
+const FINGERPRINT_BYTES: u64 = FINGERPRINT_WORDS as u64 * 4;
+
+/// `Uniforms::neighbourhood` value of a configured stencil, which overrides the rule's own.
+const STENCIL_NEIGHBOURHOOD: u32 = 2;
+
+impl Uniforms {
+    fn set_rule(&mut self, rule: &Rule) {
+        self.states = rule.states;
+        self.radius = rule.radius;
+        if self.neighbourhood != STENCIL_NEIGHBOURHOOD {
+            self.reach = rule.radius;
+            self.neighbourhood = match rule.neighbourhood {
+                Neighbourhood::Moore => 0,
+                Neighbourhood::VonNeumann => 1,
+                Neighbourhood::Hexagonal => 3,
+            };
+        }
+        self.include_centre = rule.include_centre.into();
+        self.isotropic = rule.isotropic.is_some().into();
+        self.birth = rule.birth.0;
+        self.survival = rule.survival.0;
+        self.tree_neighbours = rule.tree.as_ref().map_or(0, |tree| tree.neighbours);
+        self.tree_root = rule.tree.as_ref().map_or(0, |tree| tree.root);
+    }
 }
 
 impl Renderer {
@@ -43,72 +175,546 @@ impl Renderer {
         let compute_shader_module = compile_shader_module(&device, code);
 
         let bindgroup_layout = create_bindgroup_layout(&device);
-        let compute_pipeline =
-            create_compute_pipeline(&device, &bindgroup_layout, &compute_shader_module);
-        let render_pipeline = create_render_pipeline(&device, &shader_module, &bindgroup_layout);
-        let uniforms = Uniforms {
-            grid_size: [config.grid_size as f32, config.grid_size as f32],
+        let compute_pipeline = create_compute_pipeline(
+            &device,
+            &bindgroup_layout,
+            &compute_shader_module,
+            match (config.storage, config.kernel) {
+                _ if config.active_tiles => "activeMain",
+                (Storage::Packed, _) => "packedMain",
+                (_, Kernel::Tiled) => "tiledMain",
+                (_, Kernel::Simple) => "computeMain",
+            },
+        );
+        let prefix_pipeline = create_compute_pipeline(
+            &device,
+            &bindgroup_layout,
+            &compute_shader_module,
+            "rowPrefix",
+        );
+        let textured = config.storage == Storage::Texture;
+        // Cells in textures are drawn as a single quad reading the texture under each pixel.
+        let render_pipeline = create_render_pipeline(
+            &device,
+            &shader_module,
+            &bindgroup_layout,
+            match textured {
+                true => ["texture_vs", "texture_fs"],
+                false => ["display_vs", "display_fs"],
+            },
+            &[],
+        );
+        let overlay_pipeline = create_render_pipeline(
+            &device,
+            &shader_module,
+            &bindgroup_layout,
+            ["overlay_vs", "overlay_fs"],
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Rect>() as u64,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+            }],
+        );
+        let [width, height] = config.grid_size;
+        let packed = config.storage == Storage::Packed;
+        let unbounded = config.boundary == Boundary::Unbounded;
+        // The kernel computes an unbounded universe one chunk at a time.
+        let kernel_grid = match unbounded {
+            true => [CHUNK_SIZE; 2],
+            false => config.grid_size,
+        };
+        // An unbounded universe starts as the soup at the origin and a ring of empty chunks.
+        let chunks = unbounded.then(|| {
+            let [width, height] = [width as i32, height as i32];
+            Chunks::covering([0, 0], [width, height])
+        });
+        let capacity = chunks
+            .as_ref()
+            .map_or(1, |chunks| chunks.len().next_power_of_two());
+        let tiles = kernel_grid[0].div_ceil(TILE_SIZE) * kernel_grid[1].div_ceil(TILE_SIZE);
+        // Per tile, whether it changed, whether it is active, and its place in the list.
+        let activity_len = match config.active_tiles {
+            true => (ACTIVE_HEADER + 3 * tiles) as usize,
+            false => capacity,
+        };
+        let mut uniforms = Uniforms {
+            grid_size: kernel_grid.map(|size| size as f32),
+            topology: match config.topology {
+                Topology::Square => 0,
+                Topology::Hexagonal => 1,
+                Topology::TriangularVertex => 2,
+                Topology::TriangularEdge => 3,
+            },
+            boundary: match config.boundary {
+                Boundary::Torus { .. } => 0,
+                Boundary::Plane => 1,
+                Boundary::KleinBottle {
+                    twisted_sides: false,
+                } => 2,
+                Boundary::KleinBottle {
+                    twisted_sides: true,
+                } => 3,
+                Boundary::CrossSurface => 4,
+                Boundary::Sphere => 5,
+                Boundary::Unbounded => 6,
+            },
+            shift: match config.boundary {
+                Boundary::Torus { shift } => shift,
+                _ => [0, 0],
+            },
+            view: [0, 0, width as i32, height as i32],
+            active_tiles: config.active_tiles.into(),
+            fingerprint: activity_len as u32,
+            cell_storage: match config.storage {
+                Storage::Cells => 0,
+                Storage::Packed => 1,
+                Storage::Texture => 2,
+            },
+            ..Zeroable::zeroed()
+        };
+        let stencil = match &config.stencil {
+            Some(stencil) => {
+                uniforms.neighbourhood = STENCIL_NEIGHBOURHOOD;
+                uniforms.stencil_len = stencil.entries.len() as u32;
+                uniforms.reach = stencil.radius();
+                stencil
+                    .entries
+                    .iter()
+                    .map(|&[dx, dy, weight]| [dx, dy, weight, 0])
+                    .collect()
+            }
+            None => vec![[0; 4]],
         };
         let uniform_buffer = create_uniform_buffer(&device, &uniforms);
 
-        let mut cell_state: Vec<u32> = vec![0; (config.grid_size * config.grid_size) as usize];
-
-        {
-            let mut rng = rand::thread_rng();
-            for i in 0..cell_state.len() {
-                if rng.gen_bool(0.4) {
-                    cell_state[i] = 1;
-                }
-            }
+        // Rule trees start from a soup of all their states, other rules from live cells.
+        let live_states = config.rule.tree.as_ref().map_or(1, |tree| tree.states - 1);
+        let mut cell_state = config.soup.cells(config.grid_size, live_states);
+        if let Some(chunks) = &chunks {
+            cell_state = chunk_cells(chunks, capacity, [0, 0], config.grid_size, &cell_state);
         }
-        let storage_buffers = create_storage_buffers(&device, cell_state);
-
+        if packed {
+            cell_state = pack(&cell_state);
+        }
+        let (storage_buffers, cell_textures) = match textured {
+            true => (
+                create_storage_buffers(&device, vec![0]),
+                create_cell_textures(&device, &queue, config.grid_size, &cell_state),
+            ),
+            false => (
+                create_storage_buffers(&device, cell_state),
+                create_cell_textures(&device, &queue, [1, 1], &[0]),
+            ),
+        };
+        let shared_buffers = SharedBuffers {
+            uniforms: uniform_buffer,
+            // Packed cells only run range 1 rules, which don't use the row sums.
+            row_sums: create_row_sums_buffer(&device, if packed { [1, 1] } else { kernel_grid }),
+            stencil: create_stencil_buffer(&device, &stencil),
+            transitions: create_transitions_buffer(&device),
+            tree: create_tree_buffer(&device, 1),
+            palette: create_palette_buffer(&device),
+            chunks: create_chunks_buffer(&device, capacity),
+            activity: create_activity_buffer(&device, activity_len),
+        };
         let display_bindgroups = create_bindgroups(
             &device,
             &bindgroup_layout,
-            &uniform_buffer,
-            [&storage_buffers[0], &storage_buffers[1]],
+            &shared_buffers,
+            &storage_buffers,
+            &cell_textures,
         );
 
-        let workgroup_count: (u32, u32, u32) = (
-            (config.grid_size as f32 / 8.).ceil() as u32,
-            (config.grid_size as f32 / 8.).ceil() as u32,
-            1 as u32,
-        );
+        // The last workgroups in each direction may stick out of the grid; the kernel skips the
+        // invocations outside it.
+        // With packed cells each invocation computes a word of 32 cells.
+        let columns = if packed { width / 32 } else { kernel_grid[0] };
+        let workgroup_count: (u32, u32, u32) = (columns.div_ceil(8), kernel_grid[1].div_ceil(8), 1);
 
-        Renderer {
+        let activity_readback = create_activity_readback_buffer(&device, capacity);
+        let active_tiles = config.active_tiles.then(|| ActiveTiles {
+            tiles,
+            mark_pipeline: create_compute_pipeline(
+                &device,
+                &bindgroup_layout,
+                &compute_shader_module,
+                "markTiles",
+            ),
+            dispatch_pipeline: create_compute_pipeline(
+                &device,
+                &bindgroup_layout,
+                &compute_shader_module,
+                "activeDispatch",
+            ),
+            dispatch_args: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("active tile dispatch"),
+                size: 12,
+                usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        });
+        let tracking = config.detect.then(|| Tracking {
+            bounds_pipeline: create_compute_pipeline(
+                &device,
+                &bindgroup_layout,
+                &compute_shader_module,
+                "fingerprintBounds",
+            ),
+            cells_pipeline: create_compute_pipeline(
+                &device,
+                &bindgroup_layout,
+                &compute_shader_module,
+                "fingerprintCells",
+            ),
+            history: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("fingerprints"),
+                size: MAX_BATCH * FINGERPRINT_BYTES,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            history_readback: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("fingerprints readback"),
+                size: MAX_BATCH * FINGERPRINT_BYTES,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            detector: Detector::default(),
+            events: Vec::new(),
+        });
+        let overlay = (create_overlay_buffer(&device, 1), 0);
+        let mut renderer = Renderer {
             device,
             queue,
             compute_pipeline,
+            prefix_pipeline,
             grid_size: config.grid_size,
+            vertices_per_cell: config.topology.vertices_per_cell(),
+            uniforms,
+            bindgroup_layout,
+            shared_buffers,
+            storage_buffers,
+            cell_textures,
             render_pipeline,
+            overlay,
+            overlay_pipeline,
             display_bindgroups,
             frame_count: 0,
             workgroup_count,
-        }
+            chunks,
+            capacity,
+            outgrown: false,
+            activity_readback,
+            active_tiles,
+            tracking,
+        };
+        renderer.set_rule(&config.rule);
+        renderer.write_chunks();
+        renderer
     }
 
-    pub fn render_frame(&mut self, target: &wgpu::TextureView) {
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("render frame"),
-            });
+    /// Records the compute passes that advance the cells by one generation.
+    fn encode_generation(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(active) = &self.active_tiles {
+            self.encode_active_tiles(encoder, active);
+        }
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("compute pass"),
+            timestamp_writes: None,
+        });
 
+        compute_pass.set_bind_group(0, &(self.display_bindgroups[self.frame_count % 2]), &[]);
+
+        // On a plain torus Larger than Life neighbourhoods are counted from per-row prefix
+        // sums, so that each cell reads one value per row of its neighbourhood instead of every
+        // cell in it; other boundaries sum the neighbourhood cell by cell.
+        if self.uniforms.radius > 1
+            && self.uniforms.neighbourhood != STENCIL_NEIGHBOURHOOD
+            && self.uniforms.boundary == 0
+            && self.uniforms.shift == [0, 0]
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("compute pass"),
-                timestamp_writes: None,
-            });
+            compute_pass.set_pipeline(&self.prefix_pipeline);
+            compute_pass.dispatch_workgroups(self.grid_size[1].div_ceil(64), 1, 1);
+        }
 
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, &(self.display_bindgroups[self.frame_count % 2]), &[]);
-            compute_pass.dispatch_workgroups(
+        // An unbounded universe gets a layer of workgroups for each chunk slot.
+        let layers = self.chunks.as_ref().map_or(1, |chunks| chunks.len() as u32);
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        match &self.active_tiles {
+            Some(active) => compute_pass.dispatch_workgroups_indirect(&active.dispatch_args, 0),
+            None => compute_pass.dispatch_workgroups(
                 self.workgroup_count.0,
                 self.workgroup_count.1,
-                self.workgroup_count.2,
+                self.workgroup_count.2 * layers,
+            ),
+        }
+        drop(compute_pass);
+
+        if let Some(chunks) = self.chunks.as_ref().filter(|chunks| chunks.len() > 0) {
+            let size = (chunks.len() * 4) as u64;
+            let activity = &self.shared_buffers.activity;
+            encoder.copy_buffer_to_buffer(activity, 0, &self.activity_readback, 0, size);
+        }
+    }
+
+    /// Records the passes that list the tiles within reach of last generation's changes and set
+    /// up the kernel's dispatch over them, leaving the change flags clear for the kernel to set.
+    fn encode_active_tiles(&self, encoder: &mut wgpu::CommandEncoder, active: &ActiveTiles) {
+        let activity = &self.shared_buffers.activity;
+        let word = |i: u32| u64::from(i) * 4;
+        let tiles = active.tiles;
+        encoder.clear_buffer(activity, 0, Some(word(1)));
+        let marks = ACTIVE_HEADER + tiles;
+        encoder.clear_buffer(activity, word(marks), Some(word(tiles)));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("active tiles"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.display_bindgroups[self.frame_count % 2], &[]);
+        compute_pass.set_pipeline(&active.mark_pipeline);
+        compute_pass.dispatch_workgroups(tiles.div_ceil(64), 1, 1);
+        compute_pass.set_pipeline(&active.dispatch_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        drop(compute_pass);
+
+        encoder.copy_buffer_to_buffer(activity, word(1), &active.dispatch_args, 0, word(3));
+        encoder.clear_buffer(activity, word(ACTIVE_HEADER), Some(word(tiles)));
+    }
+
+    /// Records the passes that fingerprint the generation just computed, and copies the
+    /// fingerprint to entry `index` of the history.
+    fn encode_fingerprint(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        tracking: &Tracking,
+        index: u64,
+    ) {
+        let activity = &self.shared_buffers.activity;
+        let at = u64::from(self.uniforms.fingerprint) * 4;
+        encoder.clear_buffer(activity, at, Some(FINGERPRINT_BYTES));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("fingerprint"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.display_bindgroups[self.frame_count % 2], &[]);
+        let [columns, rows] = self
+            .uniforms
+            .grid_size
+            .map(|size| (size as u32).div_ceil(8));
+        let layers = self.chunks.as_ref().map_or(1, |chunks| chunks.len() as u32);
+        for pipeline in [&tracking.bounds_pipeline, &tracking.cells_pipeline] {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(columns, rows, layers);
+        }
+        drop(compute_pass);
+
+        let history = &tracking.history;
+        encoder.copy_buffer_to_buffer(
+            activity,
+            at,
+            history,
+            index * FINGERPRINT_BYTES,
+            FINGERPRINT_BYTES,
+        );
+    }
+
+    /// Reads back the fingerprints of the last `batch` generations and watches them for events.
+    fn read_fingerprints(&mut self, batch: u64) {
+        let Some(tracking) = &mut self.tracking else {
+            return;
+        };
+        let slice = tracking.history_readback.slice(..batch * FINGERPRINT_BYTES);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let words: Vec<u32> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        tracking.history_readback.unmap();
+        let first = self.frame_count as u64 - batch + 1;
+        for (generation, words) in (first..).zip(words.chunks_exact(FINGERPRINT_WORDS)) {
+            let fingerprint = Fingerprint::from_words(words.try_into().unwrap());
+            if let Some(event) = tracking.detector.push(generation, fingerprint) {
+                tracking.events.push((generation, event));
+            }
+        }
+    }
+
+    /// The events since the last call and the generations they happened in, when generations
+    /// are tracked.
+    pub fn events(&mut self) -> Vec<(u64, Event)> {
+        (self.tracking.as_mut())
+            .map_or_else(Vec::new, |tracking| std::mem::take(&mut tracking.events))
+    }
+
+    /// What watches the fingerprints of the generations, when they are tracked.
+    pub fn detector(&self) -> Option<&Detector> {
+        self.tracking.as_ref().map(|tracking| &tracking.detector)
+    }
+
+    /// Forgets the generations fingerprinted so far and the events not handed out yet, which say
+    /// nothing about the generations after a new rule or new cells.
+    fn reset_tracking(&mut self) {
+        if let Some(tracking) = &mut self.tracking {
+            tracking.detector = Detector::default();
+            tracking.events.clear();
+        }
+    }
+
+    /// Marks every tile as changed, so that the next generation computes them all.
+    fn mark_all_tiles(&self) {
+        if let Some(active) = &self.active_tiles {
+            let changed = vec![1u32; active.tiles as usize];
+            self.queue.write_buffer(
+                &self.shared_buffers.activity,
+                u64::from(ACTIVE_HEADER) * 4,
+                bytemuck::cast_slice(&changed),
             );
         }
+    }
+
+    /// Reads back where the last generation left live cells in an unbounded universe, and
+    /// allocates and frees chunks to match.
+    fn update_chunks(&mut self) {
+        let max_capacity = self.max_capacity();
+        let Some(chunks) = &mut self.chunks else {
+            return;
+        };
+        let mut activity = Vec::new();
+        if chunks.len() > 0 {
+            let slice = self.activity_readback.slice(..(chunks.len() * 4) as u64);
+            slice.map_async(wgpu::MapMode::Read, |_| {});
+            self.device.poll(wgpu::Maintain::Wait);
+            activity = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+            self.activity_readback.unmap();
+        }
+        let (allocated, complete) = chunks.update(&activity, max_capacity);
+        self.outgrown |= !complete;
+        let len = chunks.len();
+        if len > self.capacity {
+            self.grow(len);
+        }
+
+        // Free slots keep the cells of the chunk they last held.
+        let cells = &self.storage_buffers[self.frame_count % 2];
+        let chunk_bytes = (CHUNK_SIZE * CHUNK_SIZE * 4) as u64;
+        let zeros = vec![0; chunk_bytes as usize];
+        for slot in allocated {
+            self.queue
+                .write_buffer(cells, u64::from(slot) * chunk_bytes, &zeros);
+        }
+        self.write_chunks();
+    }
+
+    /// Uploads the chunk table of an unbounded universe and clears the activity for the next
+    /// generation.
+    fn write_chunks(&self) {
+        let Some(chunks) = self.chunks.as_ref().filter(|chunks| chunks.len() > 0) else {
+            return;
+        };
+        let buffers = &self.shared_buffers;
+        self.queue
+            .write_buffer(&buffers.chunks, 0, bytemuck::cast_slice(&chunks.entries()));
+        self.queue
+            .write_buffer(&buffers.activity, 0, &vec![0; chunks.len() * 4]);
+    }
+
+    /// The most chunks the cell buffers can grow to hold: a power of two, like every capacity,
+    /// whose cells fit in the largest buffer the GPU can bind.
+    fn max_capacity(&self) -> usize {
+        let limit = self.device.limits().max_storage_buffer_binding_size as usize;
+        let chunks = (limit / (CHUNK_SIZE * CHUNK_SIZE * 4) as usize).max(1);
+        1 << chunks.ilog2()
+    }
+
+    /// Whether a pattern in an unbounded universe has needed more chunks than the cell buffers
+    /// can hold, so that the cells that spilled beyond them were lost.
+    pub fn outgrown(&self) -> bool {
+        self.outgrown
+    }
+
+    /// Makes room for at least `len` chunks, copying the cells over to larger buffers.
+    fn grow(&mut self, len: usize) {
+        let capacity = len.next_power_of_two();
+        let cells = capacity * (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        debug_assert!(capacity <= self.max_capacity());
+
+        let storage_buffers = create_storage_buffers(&self.device, vec![0; cells]);
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        for (old, new) in self.storage_buffers.iter().zip(&storage_buffers) {
+            encoder.copy_buffer_to_buffer(old, 0, new, 0, old.size());
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        self.storage_buffers = storage_buffers;
+        self.shared_buffers.chunks = create_chunks_buffer(&self.device, capacity);
+        self.shared_buffers.activity = create_activity_buffer(&self.device, capacity);
+        self.activity_readback = create_activity_readback_buffer(&self.device, capacity);
+        self.capacity = capacity;
+        self.uniforms.fingerprint = capacity as u32;
+        self.queue.write_buffer(
+            &self.shared_buffers.uniforms,
+            0,
+            bytemuck::bytes_of(&self.uniforms),
+        );
+        self.display_bindgroups = create_bindgroups(
+            &self.device,
+            &self.bindgroup_layout,
+            &self.shared_buffers,
+            &self.storage_buffers,
+            &self.cell_textures,
+        );
+    }
+
+    /// Moves the view of an unbounded universe by `[dx, dy]` cells; bounded grids always show
+    /// the whole grid.
+    pub fn pan(&mut self, [dx, dy]: [i32; 2]) {
+        if self.chunks.is_none() {
+            return;
+        }
+        self.uniforms.view[0] += dx;
+        self.uniforms.view[1] += dy;
+        self.queue.write_buffer(
+            &self.shared_buffers.uniforms,
+            0,
+            bytemuck::bytes_of(&self.uniforms),
+        );
+    }
+
+    /// Draws `cells`, the states of the cells in view row by row from the bottom, in place of the
+    /// current generation, e.g. to display the results of another engine.
+    pub fn show(&mut self, target: &wgpu::TextureView, cells: &[u32]) {
+        let [x, y, width, height] = self.uniforms.view;
+        self.load_cells([x, y], [width as u32, height as u32], cells);
+        self.render_frame(target);
+    }
+
+    /// Sets the rectangles drawn over the cells from the next frame on.
+    pub fn set_overlay(&mut self, rects: &[Rect]) {
+        let bytes = std::mem::size_of_val(rects) as u64;
+        if bytes > self.overlay.0.size() {
+            self.overlay.0 = create_overlay_buffer(&self.device, rects.len().next_power_of_two());
+        }
+        self.queue
+            .write_buffer(&self.overlay.0, 0, bytemuck::cast_slice(rects));
+        self.overlay.1 = rects.len() as u32;
+    }
+
+    /// Blocks until the GPU has finished the work submitted so far.
+    pub fn wait(&self) {
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// The cells in view: bottom left corner, width and height.
+    pub fn view(&self) -> [i32; 4] {
+        self.uniforms.view
+    }
+
+    /// Draws the current generation.
+    pub fn render_frame(&self, target: &wgpu::TextureView) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("render frame"),
+            });
 
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("render pass"),
@@ -132,16 +738,359 @@ impl Renderer {
 
         render_pass.set_bind_group(0, &self.display_bindgroups[self.frame_count % 2], &[]);
 
-        render_pass.draw(0..6, 0..(&self.grid_size * &self.grid_size) as u32);
+        match &self.chunks {
+            None if self.uniforms.cell_storage == 2 => render_pass.draw(0..6, 0..1),
+            None => render_pass.draw(
+                0..self.vertices_per_cell,
+                0..self.grid_size[0] * self.grid_size[1],
+            ),
+            Some(chunks) => {
+                let [x, y, width, height] = self.uniforms.view;
+                let cells = CHUNK_SIZE * CHUNK_SIZE;
+                for slot in chunks.in_view([x, y], [x + width, y + height]) {
+                    render_pass.draw(0..self.vertices_per_cell, slot * cells..(slot + 1) * cells);
+                }
+            }
+        }
+
+        let (rects, len) = &self.overlay;
+        if *len > 0 {
+            render_pass.set_pipeline(&self.overlay_pipeline);
+            render_pass.set_vertex_buffer(0, rects.slice(..));
+            render_pass.draw(0..6, 0..*len);
+        }
 
         drop(render_pass);
 
         let commmand_buffer = encoder.finish();
         self.queue.submit(Some(commmand_buffer));
-        self.frame_count += 1;
+    }
+
+    /// Replaces the current generation by `cells`, a `size` region at `origin`. On a bounded grid
+    /// the region is the whole grid; in an unbounded universe nothing lives outside it.
+    fn load_cells(&mut self, origin: [i32; 2], size: [u32; 2], cells: &[u32]) {
+        self.mark_all_tiles();
+        self.reset_tracking();
+        let mut cells = cells.to_vec();
+        if self.chunks.is_some() {
+            let max = [origin[0] + size[0] as i32, origin[1] + size[1] as i32];
+            let chunks = Chunks::covering(origin, max);
+            if chunks.len() > self.capacity {
+                self.grow(chunks.len());
+            }
+            cells = chunk_cells(&chunks, self.capacity, origin, size, &cells);
+            self.chunks = Some(chunks);
+            self.write_chunks();
+        } else {
+            debug_assert_eq!((origin, size), ([0, 0], self.grid_size));
+        }
+        if self.uniforms.cell_storage == 1 {
+            cells = pack(&cells);
+        }
+        if self.uniforms.cell_storage == 2 {
+            let texture = &self.cell_textures[self.frame_count % 2];
+            write_texture(&self.queue, texture, &cells);
+            return;
+        }
+        let buffer = &self.storage_buffers[self.frame_count % 2];
+        self.queue
+            .write_buffer(buffer, 0, bytemuck::cast_slice(&cells));
+    }
+
+    /// Reads the cell buffer, or texture, of the current generation back from the GPU.
+    fn read_cells(&self) -> Vec<u32> {
+        if self.uniforms.cell_storage == 2 {
+            return self.read_texture(&self.cell_textures[self.frame_count % 2]);
+        }
+        let buffer = &self.storage_buffers[self.frame_count % 2];
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cells readback"),
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+        self.queue.submit(Some(encoder.finish()));
+        staging.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let cells = bytemuck::cast_slice(&staging.slice(..).get_mapped_range()).to_vec();
+        cells
+    }
+
+    /// Replaces the current generation. In an unbounded universe the cells fill the grid at the
+    /// origin, and nothing lives outside it.
+    pub fn set_cells(&mut self, cells: &[u32]) {
+        self.load_cells([0, 0], self.grid_size, cells);
+    }
+
+    /// The live cells of an unbounded universe.
+    pub fn live_cells(&self) -> Vec<[i32; 2]> {
+        let buffer = self.read_cells();
+        let chunks = self.chunks.as_ref().expect("an unbounded universe");
+        let size = CHUNK_SIZE as i32;
+        let mut cells = Vec::new();
+        for (slot, [cx, cy]) in chunks.iter() {
+            for y in 0..size {
+                for x in 0..size {
+                    if buffer[((slot as i32 * size + y) * size + x) as usize] != 0 {
+                        cells.push([cx * size + x, cy * size + y]);
+                    }
+                }
+            }
+        }
+        cells.sort();
+        cells
+    }
+
+    /// A texture to draw frames into instead of a window's surface.
+    pub fn offscreen_target(&self, [width, height]: [u32; 2]) -> wgpu::Texture {
+        self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen frame"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FRAME_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Reads a frame drawn into an offscreen target back as RGB pixels, top row first.
+    pub fn read_frame(&self, target: &wgpu::Texture) -> Vec<[u8; 3]> {
+        (self.read_texture(target).into_iter())
+            .map(|pixel| {
+                let [b, g, r, _] = pixel.to_le_bytes();
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    /// Reads a texture of four byte texels back from the GPU, row by row.
+    fn read_texture(&self, texture: &wgpu::Texture) -> Vec<u32> {
+        let [width, height] = [texture.width(), texture.height()];
+        // Rows of a copy out of a texture start at multiples of 256 bytes.
+        let row_bytes = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture readback"),
+            size: u64::from(row_bytes * height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+        staging.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let mapped = staging.slice(..).get_mapped_range();
+        (mapped.chunks(row_bytes as usize))
+            .flat_map(|row| bytemuck::cast_slice::<u8, u32>(&row[..width as usize * 4]))
+            .copied()
+            .collect()
     }
 }
 
+impl Simulation for Renderer {
+    fn set_rule(&mut self, rule: &Rule) {
+        self.uniforms.set_rule(rule);
+        // Cells that were settled under the old rule may not be under the new one.
+        self.mark_all_tiles();
+        self.reset_tracking();
+        let buffers = &self.shared_buffers;
+        self.queue
+            .write_buffer(&buffers.uniforms, 0, bytemuck::bytes_of(&self.uniforms));
+        if let Some(isotropic) = &rule.isotropic {
+            self.queue.write_buffer(
+                &buffers.transitions,
+                0,
+                bytemuck::cast_slice(&isotropic.transitions()),
+            );
+        }
+        if let Some(tree) = &rule.tree {
+            // Trees vary in size, so a larger one gets a new buffer and new bind groups.
+            if std::mem::size_of_val(tree.nodes.as_slice()) as u64 > buffers.tree.size() {
+                self.shared_buffers.tree = create_tree_buffer(&self.device, tree.nodes.len());
+                self.display_bindgroups = create_bindgroups(
+                    &self.device,
+                    &self.bindgroup_layout,
+                    &self.shared_buffers,
+                    &self.storage_buffers,
+                    &self.cell_textures,
+                );
+            }
+            let buffers = &self.shared_buffers;
+            self.queue
+                .write_buffer(&buffers.tree, 0, bytemuck::cast_slice(&tree.nodes));
+            let mut palette = [[0f32; 4]; MAX_PALETTE];
+            for (colour, &[r, g, b]) in palette.iter_mut().zip(&tree.colours) {
+                *colour = [r, g, b, 255].map(|channel| channel as f32 / 255.);
+            }
+            self.queue
+                .write_buffer(&buffers.palette, 0, bytemuck::cast_slice(&palette));
+        }
+    }
+
+    fn step(&mut self, generations: u64) {
+        let mut remaining = generations;
+        // Generations fingerprinted and not read back yet.
+        let mut fingerprinted = 0;
+        while remaining > 0 {
+            // Bounded grids submit generations in batches; an unbounded universe has to read
+            // back after each one which chunks it needs next.
+            let batch = match self.chunks {
+                Some(_) => 1,
+                None => remaining.min(MAX_BATCH),
+            };
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            for _ in 0..batch {
+                self.encode_generation(&mut encoder);
+                self.frame_count += 1;
+                if let Some(tracking) = &self.tracking {
+                    self.encode_fingerprint(&mut encoder, tracking, fingerprinted);
+                }
+                fingerprinted += 1;
+            }
+            remaining -= batch;
+            // The fingerprints are read back together once the history is full or the last
+            // generation is done.
+            let read = remaining == 0 || fingerprinted == MAX_BATCH;
+            if let Some(tracking) = self.tracking.as_ref().filter(|_| read) {
+                let bytes = fingerprinted * FINGERPRINT_BYTES;
+                encoder.copy_buffer_to_buffer(
+                    &tracking.history,
+                    0,
+                    &tracking.history_readback,
+                    0,
+                    bytes,
+                );
+            }
+            self.queue.submit(Some(encoder.finish()));
+            if read {
+                self.read_fingerprints(fingerprinted);
+                fingerprinted = 0;
+            }
+            self.update_chunks();
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.frame_count as u64
+    }
+
+    fn population(&self) -> u64 {
+        let mut cells = self.read_cells();
+        if self.uniforms.cell_storage == 1 {
+            cells = unpack(&cells);
+        }
+        let live = |cells: &[u32]| cells.iter().filter(|&&state| state == 1).count() as u64;
+        match &self.chunks {
+            None => live(&cells),
+            // Free slots hold leftovers.
+            Some(chunks) => {
+                let size = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+                (chunks.iter())
+                    .map(|(slot, _)| live(&cells[slot as usize * size..][..size]))
+                    .sum()
+            }
+        }
+    }
+
+    /// Cells outside a bounded grid read as dead.
+    fn region(&self, origin: [i64; 2], [width, height]: [u32; 2]) -> Vec<u32> {
+        let mut buffer = self.read_cells();
+        if self.uniforms.cell_storage == 1 {
+            buffer = unpack(&buffer);
+        }
+        let [grid_width, grid_height] = self.grid_size.map(i64::from);
+        let index = |[x, y]: [i64; 2]| match &self.chunks {
+            Some(chunks) => chunks.cell_index([x as i32, y as i32]),
+            None => ((0..grid_width).contains(&x) && (0..grid_height).contains(&y))
+                .then_some((y * grid_width + x) as usize),
+        };
+        (0..i64::from(height))
+            .flat_map(|y| (0..i64::from(width)).map(move |x| [origin[0] + x, origin[1] + y]))
+            .map(|cell| index(cell).map_or(0, |i| buffer[i]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Renderer {
+    /// The current generation. In an unbounded universe, the cells of the grid at the origin.
+    pub fn cells(&self) -> Vec<u32> {
+        self.region([0, 0], self.grid_size)
+    }
+
+    /// Number of chunks an unbounded universe is keeping.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks
+            .as_ref()
+            .map_or(0, |chunks| chunks.iter().count())
+    }
+
+    /// A renderer without a window, or `None` on machines without any adapter.
+    pub fn headless(config: &Config) -> Option<Renderer> {
+        Renderer::connected(config, false)
+    }
+
+    /// A renderer on a software adapter such as llvmpipe, which behaves the same on every
+    /// machine, or `None` on machines without one.
+    pub fn fallback(config: &Config) -> Option<Renderer> {
+        Renderer::connected(config, true)
+    }
+
+    fn connected(config: &Config, fallback: bool) -> Option<Renderer> {
+        match pollster::block_on(connect_headless(fallback)) {
+            Ok((device, queue)) => Some(Renderer::new(device, queue, config)),
+            Err(error) => {
+                eprintln!("skipping: {error}");
+                None
+            }
+        }
+    }
+}
+
+/// Connects to a GPU without a surface to draw to, falling back on a software adapter such as
+/// llvmpipe when there is no GPU, or always with `fallback`.
+pub async fn connect_headless(fallback: bool) -> Result<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::default();
+    let options = |force_fallback_adapter| wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        force_fallback_adapter,
+        compatible_surface: None,
+    };
+    let adapter = match fallback {
+        true => None,
+        false => instance.request_adapter(&options(false)).await,
+    };
+    let adapter = match adapter {
+        Some(adapter) => adapter,
+        None => (instance.request_adapter(&options(true)).await)
+            .context("failed to find an adapter, even a software one")?,
+    };
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor::default(), None)
+        .await
+        .context("failed to connect to the GPU")?;
+    Ok((device, queue))
+}
+
 fn compile_shader_module(device: &wgpu::Device, code: &str) -> wgpu::ShaderModule {
     use std::borrow::Cow;
 
@@ -155,6 +1104,8 @@ fn create_render_pipeline(
     device: &wgpu::Device,
     shader_module: &wgpu::ShaderModule,
     bind_group_layout: &wgpu::BindGroupLayout,
+    [vertex_entry_point, fragment_entry_point]: [&str; 2],
+    buffers: &[wgpu::VertexBufferLayout],
 ) -> wgpu::RenderPipeline {
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("display"),
@@ -172,15 +1123,15 @@ fn create_render_pipeline(
         },
         vertex: wgpu::VertexState {
             module: shader_module,
-            entry_point: "display_vs",
-            buffers: &[],
+            entry_point: vertex_entry_point,
+            buffers,
             compilation_options: PipelineCompilationOptions::default(),
         },
         fragment: Some(wgpu::FragmentState {
             module: shader_module,
-            entry_point: "display_fs",
+            entry_point: fragment_entry_point,
             targets: &[Some(wgpu::ColorTargetState {
-                format: wgpu::TextureFormat::Bgra8Unorm,
+                format: FRAME_FORMAT,
                 blend: None,
                 write_mask: wgpu::ColorWrites::ALL,
             })],
@@ -208,23 +1159,40 @@ fn create_bindgroup_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
                 },
                 count: None,
             },
+            storage_layout_entry(
+                1,
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::COMPUTE,
+                true,
+            ),
+            storage_layout_entry(2, wgpu::ShaderStages::COMPUTE, false),
+            storage_layout_entry(3, wgpu::ShaderStages::COMPUTE, false),
+            storage_layout_entry(4, wgpu::ShaderStages::COMPUTE, true),
+            storage_layout_entry(5, wgpu::ShaderStages::COMPUTE, true),
+            storage_layout_entry(6, wgpu::ShaderStages::COMPUTE, true),
+            storage_layout_entry(7, wgpu::ShaderStages::FRAGMENT, true),
+            storage_layout_entry(
+                8,
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::COMPUTE,
+                true,
+            ),
+            storage_layout_entry(9, wgpu::ShaderStages::COMPUTE, false),
             wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Uint,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
                 count: None,
             },
             wgpu::BindGroupLayoutEntry {
-                binding: 2,
+                binding: 11,
                 visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: CELL_TEXTURE_FORMAT,
+                    view_dimension: wgpu::TextureViewDimension::D2,
                 },
                 count: None,
             },
@@ -232,81 +1200,91 @@ fn create_bindgroup_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     })
 }
 
+fn storage_layout_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// The buffers bound next to the ping-pong cell state buffers.
+struct SharedBuffers {
+    uniforms: wgpu::Buffer,
+    row_sums: wgpu::Buffer,
+    stencil: wgpu::Buffer,
+    transitions: wgpu::Buffer,
+    tree: wgpu::Buffer,
+    palette: wgpu::Buffer,
+    chunks: wgpu::Buffer,
+    activity: wgpu::Buffer,
+}
+
 fn create_bindgroups(
     device: &wgpu::Device,
     layout: &wgpu::BindGroupLayout,
-    uniform_buffer: &wgpu::Buffer,
-    storagebuffers: [&wgpu::Buffer; 2],
+    shared: &SharedBuffers,
+    storagebuffers: &[wgpu::Buffer; 2],
+    textures: &[wgpu::Texture; 2],
 ) -> [wgpu::BindGroup; 2] {
-    [
-        device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: uniform_buffer,
-                        offset: 0,
-                        size: None,
-                    }),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: storagebuffers[0],
-                        offset: 0,
-                        size: None,
-                    }),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: storagebuffers[1],
-                        offset: 0,
-                        size: None,
-                    }),
-                },
-            ],
-        }),
+    let views = textures
+        .each_ref()
+        .map(|texture| texture.create_view(&Default::default()));
+    let create_bindgroup = |input: usize, output: usize| {
+        let buffers = [
+            &shared.uniforms,
+            &storagebuffers[input],
+            &storagebuffers[output],
+            &shared.row_sums,
+            &shared.stencil,
+            &shared.transitions,
+            &shared.tree,
+            &shared.palette,
+            &shared.chunks,
+            &shared.activity,
+        ];
+        let mut entries: Vec<_> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        entries.extend([
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: wgpu::BindingResource::TextureView(&views[input]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::TextureView(&views[output]),
+            },
+        ]);
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
-            layout: layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: uniform_buffer,
-                        offset: 0,
-                        size: None,
-                    }),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: storagebuffers[1],
-                        offset: 0,
-                        size: None,
-                    }),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer: storagebuffers[0],
-                        offset: 0,
-                        size: None,
-                    }),
-                },
-            ],
-        }),
-    ]
+            layout,
+            entries: &entries,
+        })
+    };
+
+    [create_bindgroup(0, 1), create_bindgroup(1, 0)]
 }
 
 fn create_uniform_buffer(device: &wgpu::Device, uniforms: &Uniforms) -> wgpu::Buffer {
     let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("uniforms"),
         size: std::mem::size_of::<Uniforms>() as u64,
-        usage: wgpu::BufferUsages::UNIFORM,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: true,
     });
     uniform_buffer
@@ -321,7 +1299,9 @@ fn create_storage_buffers(device: &wgpu::Device, initial_state: Vec<u32>) -> [wg
     let buffer1 = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("storage buffer 1"),
         size: (initial_state.len() * 4) as u64,
-        usage: wgpu::BufferUsages::STORAGE,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: true,
     });
     buffer1
@@ -333,7 +1313,9 @@ fn create_storage_buffers(device: &wgpu::Device, initial_state: Vec<u32>) -> [wg
     let buffer2 = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("storage buffer 2"),
         size: (initial_state.len() * 4) as u64,
-        usage: wgpu::BufferUsages::STORAGE,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: true,
     });
     buffer2
@@ -345,13 +1327,206 @@ fn create_storage_buffers(device: &wgpu::Device, initial_state: Vec<u32>) -> [wg
     [buffer1, buffer2]
 }
 
+/// Format of the cell textures: a state per texel, as in the cell buffers. Storage textures
+/// can't be `r8uint` without adapter specific features.
+const CELL_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+fn create_cell_textures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    [width, height]: [u32; 2],
+    initial_state: &[u32],
+) -> [wgpu::Texture; 2] {
+    let create_texture = |label| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CELL_TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    };
+    let textures = [
+        create_texture("cell texture 1"),
+        create_texture("cell texture 2"),
+    ];
+    for texture in &textures {
+        write_texture(queue, texture, initial_state);
+    }
+    textures
+}
+
+/// Replaces the cells in a cell texture, row by row.
+fn write_texture(queue: &wgpu::Queue, texture: &wgpu::Texture, cells: &[u32]) {
+    queue.write_texture(
+        texture.as_image_copy(),
+        bytemuck::cast_slice(cells),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(texture.width() * 4),
+            rows_per_image: None,
+        },
+        texture.size(),
+    );
+}
+
+/// Packs cells into words, cell `i` going into bit `i % 32` of word `i / 32`.
+fn pack(cells: &[u32]) -> Vec<u32> {
+    cells
+        .chunks(32)
+        .map(|word| {
+            (word.iter().enumerate())
+                .fold(0, |bits, (bit, &cell)| bits | u32::from(cell != 0) << bit)
+        })
+        .collect()
+}
+
+fn unpack(words: &[u32]) -> Vec<u32> {
+    (words.iter())
+        .flat_map(|&word| (0..32).map(move |bit| word >> bit & 1))
+        .collect()
+}
+
+/// Scratch space for the per-row prefix sums of live cells, one row of `width + 1` entries for
+/// each row of the grid.
+fn create_row_sums_buffer(device: &wgpu::Device, [width, height]: [u32; 2]) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("row sums"),
+        size: ((width + 1) * height * 4) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })
+}
+
+/// The `(dx, dy, weight, _)` entries of the stencil; a single unused entry without one.
+fn create_stencil_buffer(device: &wgpu::Device, entries: &[[i32; 4]]) -> wgpu::Buffer {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("stencil"),
+        size: std::mem::size_of_val(entries) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: true,
+    });
+    buffer
+        .slice(..)
+        .get_mapped_range_mut()
+        .copy_from_slice(bytemuck::cast_slice(entries));
+    buffer.unmap();
+    buffer
+}
+
+/// The transition table of isotropic non-totalistic rules, indexed by neighbourhood configuration.
+fn create_transitions_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("transitions"),
+        size: 256 * 4,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// The flattened nodes of a rule tree, room for at least `len` entries.
+fn create_tree_buffer(device: &wgpu::Device, len: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("rule tree"),
+        size: (len.max(1) * 4) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Number of colours in the palette of rule tree states.
+const MAX_PALETTE: usize = Rule::MAX_STATES as usize;
+
+/// The RGBA colour of each state of a rule tree.
+fn create_palette_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("palette"),
+        size: (MAX_PALETTE * 16) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Room for `len` rectangles of the overlay.
+fn create_overlay_buffer(device: &wgpu::Device, len: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("overlay"),
+        size: (len * std::mem::size_of::<Rect>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// The `ChunkEntry` of each slot of an unbounded universe.
+fn create_chunks_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("chunks"),
+        size: (capacity * std::mem::size_of::<ChunkEntry>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// The activity mask the kernel reports for each slot of an unbounded universe, or the tiles'
+/// activity with active tiles, followed by the fingerprint of a generation.
+fn create_activity_buffer(device: &wgpu::Device, len: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("chunk activity"),
+        size: ((len + FINGERPRINT_WORDS) * 4) as u64,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_activity_readback_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("chunk activity readback"),
+        size: (capacity * 4) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    })
+}
+
+/// Lays the cells of the `[width, height]` region at `origin` out in the slots of their chunks.
+fn chunk_cells(
+    chunks: &Chunks,
+    capacity: usize,
+    origin: [i32; 2],
+    [width, height]: [u32; 2],
+    cells: &[u32],
+) -> Vec<u32> {
+    let mut pool = vec![0; capacity * (CHUNK_SIZE * CHUNK_SIZE) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let i = chunks
+                .cell_index([origin[0] + x as i32, origin[1] + y as i32])
+                .expect("the region's chunks are stored");
+            pool[i] = cells[(y * width + x) as usize];
+        }
+    }
+    pool
+}
+
 fn create_compute_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::BindGroupLayout,
     shader_module: &wgpu::ShaderModule,
+    entry_point: &str,
 ) -> wgpu::ComputePipeline {
     device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("add source"),
+        label: Some(entry_point),
         layout: Some(
             &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 bind_group_layouts: &[layout],
@@ -359,7 +1534,128 @@ fn create_compute_pipeline(
             }),
         ),
         module: shader_module,
-        entry_point: "computeMain",
+        entry_point,
         compilation_options: wgpu::PipelineCompilationOptions::default(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Renderer;
+    use crate::{
+        reference::{self, expected_step, BOUNDARIES},
+        simulation::Simulation,
+        Config,
+    };
+
+    #[test]
+    fn offscreen_frames_show_the_cells() {
+        for storage in ["cells", "packed", "texture"] {
+            let args = ["--grid-size", "32", "--storage", storage];
+            let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+            let Some(mut renderer) = Renderer::headless(&config) else {
+                return;
+            };
+            // A live cell in the bottom left corner of the grid.
+            let mut cells = vec![0; 32 * 32];
+            cells[0] = 1;
+            renderer.set_cells(&cells);
+            let target = renderer.offscreen_target([320, 320]);
+            renderer.render_frame(&target.create_view(&Default::default()));
+            let frame = renderer.read_frame(&target);
+            assert_eq!(frame.len(), 320 * 320);
+            let background = [0, 0, 102];
+            let pixel = |[x, y]: [usize; 2]| frame[y * 320 + x];
+            assert_ne!(pixel([5, 315]), background, "{storage}");
+            assert_eq!(pixel([315, 5]), background, "{storage}");
+            assert_eq!(pixel([15, 315]), background, "{storage}");
+        }
+    }
+
+    #[test]
+    fn packed_cells() {
+        for spec in ["T", "P"] {
+            for size in [[64, 40], [32, 7]] {
+                for rule in ["B3/S23", "B36/S23", "B2/S", "B1357/S02468"] {
+                    reference::check(spec, size, rule, &["--storage", "packed"]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiled_kernel() {
+        for spec in BOUNDARIES {
+            for size in [[30, 17], [9, 9], [8, 16]] {
+                let size = if spec == "S" { [size[0]; 2] } else { size };
+                for rule in ["B3/S23", "B36/S23", "B2/S"] {
+                    reference::check(spec, size, rule, &["--kernel", "tiled"]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn texture_cells() {
+        for spec in BOUNDARIES {
+            let size = if spec == "S" { [30, 30] } else { [30, 17] };
+            reference::check(spec, size, "B3/S23", &["--storage", "texture"]);
+            reference::check(
+                spec,
+                size,
+                "R2,C0,M1,S3..5,B3..4,NM",
+                &["--storage", "texture"],
+            );
+            let tiled = ["--storage", "texture", "--kernel", "tiled"];
+            reference::check(spec, size, "B36/S23", &tiled);
+        }
+    }
+
+    #[test]
+    fn active_tiles() {
+        for spec in BOUNDARIES {
+            let size = if spec == "S" { [30, 30] } else { [30, 17] };
+            reference::check(spec, size, "B3/S23", &["--active-tiles"]);
+            reference::check(spec, size, "R2,C0,M1,S3..5,B3..4,NM", &["--active-tiles"]);
+            let textured = ["--active-tiles", "--storage", "texture"];
+            reference::check(spec, size, "B36/S23", &textured);
+        }
+    }
+
+    #[test]
+    fn active_tiles_follow_gliders_across_edges() {
+        // Gliders heading into the bottom left corner and the right edge, a blinker and a block,
+        // on a grid that doesn't divide into whole tiles.
+        let glider = [[1, 0], [0, 1], [0, 2], [1, 2], [2, 2]];
+        let mut live: Vec<[usize; 2]> = glider.iter().map(|&[x, y]| [x + 4, y + 3]).collect();
+        live.extend(glider.iter().map(|&[x, y]| [30 - x, y + 14]));
+        live.extend([
+            [18, 24],
+            [18, 25],
+            [18, 26],
+            [8, 22],
+            [9, 22],
+            [8, 23],
+            [9, 23],
+        ]);
+        for spec in BOUNDARIES {
+            let [width, height] = if spec == "S" { [35, 35] } else { [35, 29] };
+            let size = format!("{width}x{height}");
+            let args = ["--boundary", spec, "--grid-size", &size, "--active-tiles"];
+            let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+            let Some(mut renderer) = Renderer::headless(&config) else {
+                return;
+            };
+            let mut cells = vec![0; (width * height) as usize];
+            for &[x, y] in &live {
+                cells[y * width as usize + x] = 1;
+            }
+            renderer.set_cells(&cells);
+            for generation in 1..=120 {
+                renderer.step(1);
+                cells = expected_step(config.boundary, [width, height], &config.rule, &cells);
+                assert_eq!(renderer.cells(), cells, "generation {generation} on {spec}");
+            }
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::{fmt, path::Path, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::{
+    golly,
+    isotropic::{Configurations, Isotropic},
+    ruletree::{self, RuleTree},
+};
+
+/// A totalistic rule: a cell is born or survives when the number of live cells in its
+/// neighbourhood is one of the counts in the corresponding set.
+///
+/// Rules with more than two `states` belong to the Generations family: a live cell that does not
+/// survive passes through `states - 2` refractory states before it is dead, and only fully alive
+/// cells (state 1) count as neighbours.
+///
+/// Life-like rules use the radius 1 Moore neighbourhood; Larger than Life rules widen it up to
+/// [`Rule::MAX_RADIUS`] and may count the centre cell as well. Isotropic non-totalistic rules
+/// replace the birth and survival counts by sets of neighbourhood configurations.
+///
+/// Any other multi-state rule, such as Wireworld, is given by a [`RuleTree`] that replaces the
+/// counts and the Generations decay altogether.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub birth: Counts,
+    pub survival: Counts,
+    pub states: u32,
+    pub neighbourhood: Neighbourhood,
+    pub radius: u32,
+    pub include_centre: bool,
+    pub isotropic: Option<Isotropic>,
+    pub tree: Option<Arc<RuleTree>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// Every cell within Chebyshev distance `radius`.
+    Moore,
+    /// Every cell within Manhattan distance `radius`.
+    VonNeumann,
+    /// The six neighbours of a hexagonal cell; emulated on the square grid by the Moore
+    /// neighbourhood without its NE and SW corners.
+    Hexagonal,
+}
+
+/// A set of neighbour counts, stored as a bitmask wide enough for the largest neighbourhood.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counts(pub [u32; 16]);
+
+impl Counts {
+    /// Number of distinct counts a set can hold.
+    pub const CAPACITY: u32 = 512;
+
+    pub fn contains(&self, count: u32) -> bool {
+        count < Self::CAPACITY && self.0[count as usize / 32] & (1 << (count % 32)) != 0
+    }
+
+    pub fn insert(&mut self, count: u32) {
+        self.0[count as usize / 32] |= 1 << (count % 32);
+    }
+
+    /// The counts in the set grouped into inclusive ranges.
+    fn ranges(&self) -> Vec<(u32, u32)> {
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for count in (0..Self::CAPACITY).filter(|&count| self.contains(count)) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == count => *end = count,
+                _ => ranges.push((count, count)),
+            }
+        }
+        ranges
+    }
+}
+
+impl Rule {
+    /// Conway's game of life, `B3/S23`.
+    pub const CONWAY: Rule = Rule {
+        birth: Counts([1 << 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+        survival: Counts([1 << 2 | 1 << 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+        states: 2,
+        neighbourhood: Neighbourhood::Moore,
+        radius: 1,
+        include_centre: false,
+        isotropic: None,
+        tree: None,
+    };
+
+    /// Largest number of states a Generations rule may have.
+    pub const MAX_STATES: u32 = 256;
+
+    /// Largest neighbourhood radius of a Larger than Life rule.
+    pub const MAX_RADIUS: u32 = 10;
+
+    /// A rule running the transition function of `tree`.
+    pub fn from_tree(tree: RuleTree) -> Rule {
+        Rule {
+            birth: Counts::default(),
+            survival: Counts::default(),
+            states: tree.states,
+            neighbourhood: match tree.neighbours {
+                4 => Neighbourhood::VonNeumann,
+                _ => Neighbourhood::Moore,
+            },
+            tree: Some(Arc::new(tree)),
+            ..Rule::CONWAY
+        }
+    }
+
+    /// Whether dead cells without live neighbours come alive, as under B0 rules, which would fill
+    /// an unbounded plane in one generation.
+    pub fn births_from_nothing(&self) -> bool {
+        match (&self.tree, &self.isotropic) {
+            (Some(tree), _) => tree.next(&vec![0; tree.neighbours as usize + 1]) != 0,
+            (None, Some(isotropic)) => isotropic.birth.contains(0),
+            (None, None) => self.birth.contains(0),
+        }
+    }
+
+    /// Whether this rule can be written in B/S notation.
+    pub fn is_life_like(&self) -> bool {
+        // Counts above 8 only occur on larger neighbourhoods such as the triangular lattice's.
+        let single_digit = |counts: &Counts| (9..Counts::CAPACITY).all(|n| !counts.contains(n));
+        self.neighbourhood != Neighbourhood::VonNeumann
+            && self.radius == 1
+            && !self.include_centre
+            && single_digit(&self.birth)
+            && single_digit(&self.survival)
+    }
+
+    fn counts_mut(&mut self, field: char) -> &mut Counts {
+        match field {
+            'B' => &mut self.birth,
+            _ => &mut self.survival,
+        }
+    }
+
+    /// Parses a Larger than Life rule such as `R5,C0,M1,S34..58,B34..45,NM`.
+    fn parse_ltl(rulestring: &str) -> Result<Rule> {
+        let mut rule = Rule {
+            birth: Counts::default(),
+            survival: Counts::default(),
+            ..Rule::CONWAY
+        };
+        // The S or B field that bare ranges such as the `5..6` in `S2..3,5..6` extend.
+        let mut ranges_of = None;
+        for part in rulestring.split(',').map(str::trim) {
+            let Some(tag) = part.chars().next() else {
+                bail!("invalid rulestring {rulestring:?}: empty field")
+            };
+            let value = &part[tag.len_utf8()..];
+            let tag = tag.to_ascii_uppercase();
+            match tag {
+                'R' => rule.radius = parse_number(rulestring, value)?,
+                'C' => rule.states = parse_number::<u32>(rulestring, value)?.max(2),
+                'M' => rule.include_centre = parse_number::<u32>(rulestring, value)? == 1,
+                'N' => {
+                    rule.neighbourhood = match value {
+                        "M" | "m" => Neighbourhood::Moore,
+                        "N" | "n" => Neighbourhood::VonNeumann,
+                        _ => bail!(
+                            "invalid rulestring {rulestring:?}: unknown neighbourhood {value:?}"
+                        ),
+                    }
+                }
+                'S' | 'B' if !value.is_empty() => {
+                    parse_range(rulestring, value, rule.counts_mut(tag))?
+                }
+                'S' | 'B' => {}
+                '0'..='9' => match ranges_of {
+                    Some(field) => parse_range(rulestring, part, rule.counts_mut(field))?,
+                    None => bail!("invalid rulestring {rulestring:?}: unexpected range {part:?}"),
+                },
+                _ => bail!("invalid rulestring {rulestring:?}: unknown field {part:?}"),
+            }
+            ranges_of = match tag {
+                'S' | 'B' => Some(tag),
+                '0'..='9' => ranges_of,
+                _ => None,
+            };
+        }
+
+        ensure!(
+            (1..=Rule::MAX_RADIUS).contains(&rule.radius),
+            "invalid rulestring {rulestring:?}: the radius must be between 1 and {}",
+            Rule::MAX_RADIUS
+        );
+        ensure!(
+            rule.states <= Rule::MAX_STATES,
+            "invalid rulestring {rulestring:?}: the number of states must be at most {}",
+            Rule::MAX_STATES
+        );
+        Ok(rule)
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::CONWAY
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    /// Parses a rulestring in `B3/S23` notation. The legacy `S/B` form (`23/3`) is accepted too.
+    /// Generations rules append the number of states, as in `B2/S/C3` or `/2/3` (Brian's Brain).
+    /// Isotropic non-totalistic conditions are written in Hensel notation, as in `B2-a/S12`.
+    /// Larger than Life rules use the `R5,C0,M1,S34..58,B34..45,NM` notation. A trailing `H`, as
+    /// in `B2/S34H`, selects the hexagonal neighbourhood. `WireWorld` names the built-in
+    /// Wireworld rule, and a path ending in `.rule` loads a Golly rule file.
+    fn from_str(rulestring: &str) -> Result<Rule> {
+        let rulestring = rulestring.trim();
+        if rulestring.eq_ignore_ascii_case("wireworld") {
+            return Ok(Rule::from_tree(ruletree::wireworld()));
+        }
+        if rulestring.to_ascii_lowercase().ends_with(".rule") {
+            return Ok(Rule::from_tree(golly::load(Path::new(rulestring))?));
+        }
+        if rulestring.starts_with(['R', 'r']) {
+            return Rule::parse_ltl(rulestring);
+        }
+        let (rulestring_bs, neighbourhood) = match rulestring.strip_suffix(['H', 'h']) {
+            Some(rest) => (rest, Neighbourhood::Hexagonal),
+            None => (rulestring, Neighbourhood::Moore),
+        };
+
+        let (rulestring_bs, states) = match rulestring_bs.rsplit_once('/') {
+            Some((rest, states)) if rest.contains('/') => (rest, parse_states(rulestring, states)?),
+            _ => (rulestring_bs, 2),
+        };
+        let (birth, survival) = match rulestring_bs.split_once('/') {
+            Some((first, second)) => {
+                let (first_tag, first) = split_tag(first);
+                let (second_tag, second) = split_tag(second);
+                match (first_tag, second_tag) {
+                    (Some('b'), Some('s')) => (first, second),
+                    (Some('s'), Some('b')) => (second, first),
+                    (None, None) => (second, first),
+                    _ => bail!("invalid rulestring {rulestring:?}: expected the form B3/S23"),
+                }
+            }
+            // `B3S23` without a separator.
+            None => {
+                let lower = rulestring_bs.to_ascii_lowercase();
+                match (lower.starts_with('b'), lower.find('s')) {
+                    (true, Some(s)) => (&rulestring_bs[1..s], &rulestring_bs[s + 1..]),
+                    _ => bail!("invalid rulestring {rulestring:?}: expected the form B3/S23"),
+                }
+            }
+        };
+
+        if (birth.chars().chain(survival.chars())).any(|c| c.is_ascii_alphabetic()) {
+            ensure!(
+                neighbourhood == Neighbourhood::Moore,
+                "invalid rulestring {rulestring:?}: isotropic conditions need the Moore neighbourhood"
+            );
+            return Ok(Rule {
+                birth: Counts::default(),
+                survival: Counts::default(),
+                states,
+                neighbourhood,
+                isotropic: Some(Isotropic {
+                    birth: Configurations::parse(rulestring, birth)?,
+                    survival: Configurations::parse(rulestring, survival)?,
+                }),
+                ..Rule::CONWAY
+            });
+        }
+
+        Ok(Rule {
+            birth: parse_counts(rulestring, birth)?,
+            survival: parse_counts(rulestring, survival)?,
+            states,
+            neighbourhood,
+            ..Rule::CONWAY
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(tree) = &self.tree {
+            return write!(f, "{}", tree.name);
+        }
+        if !self.is_life_like() {
+            let states = if self.states > 2 { self.states } else { 0 };
+            write!(
+                f,
+                "R{},C{},M{},S",
+                self.radius,
+                states,
+                u8::from(self.include_centre)
+            )?;
+            write_ranges(f, &self.survival)?;
+            write!(f, ",B")?;
+            write_ranges(f, &self.birth)?;
+            let neighbourhood = match self.neighbourhood {
+                Neighbourhood::VonNeumann => 'N',
+                _ => 'M',
+            };
+            return write!(f, ",N{neighbourhood}");
+        }
+
+        match &self.isotropic {
+            Some(isotropic) => write!(f, "B{}/S{}", isotropic.birth, isotropic.survival)?,
+            None => {
+                write!(f, "B")?;
+                write_counts(f, &self.birth)?;
+                write!(f, "/S")?;
+                write_counts(f, &self.survival)?;
+            }
+        }
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        if self.neighbourhood == Neighbourhood::Hexagonal {
+            write!(f, "H")?;
+        }
+        Ok(())
+    }
+}
+
+fn split_tag(part: &str) -> (Option<char>, &str) {
+    match part.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => (Some(c.to_ascii_lowercase()), &part[1..]),
+        _ => (None, part),
+    }
+}
+
+fn parse_counts(rulestring: &str, counts: &str) -> Result<Counts> {
+    let mut set = Counts::default();
+    for c in counts.chars() {
+        let count = c.to_digit(10);
+        ensure!(
+            matches!(count, Some(0..=8)),
+            "invalid rulestring {rulestring:?}: {c:?} is not a neighbour count between 0 and 8"
+        );
+        set.insert(count.unwrap());
+    }
+    Ok(set)
+}
+
+fn parse_states(rulestring: &str, states: &str) -> Result<u32> {
+    let digits = states.trim_start_matches(['C', 'c', 'G', 'g']);
+    let states: u32 = digits.parse().map_err(|_| {
+        anyhow!("invalid rulestring {rulestring:?}: {states:?} is not a number of states")
+    })?;
+    ensure!(
+        (2..=Rule::MAX_STATES).contains(&states),
+        "invalid rulestring {rulestring:?}: the number of states must be between 2 and {}",
+        Rule::MAX_STATES
+    );
+    Ok(states)
+}
+
+fn parse_number<T: FromStr>(rulestring: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid rulestring {rulestring:?}: {value:?} is not a number"))
+}
+
+/// Parses `34..58` (or a single count) into `counts`.
+fn parse_range(rulestring: &str, range: &str, counts: &mut Counts) -> Result<()> {
+    let (start, end) = range.split_once("..").unwrap_or((range, range));
+    let start: u32 = parse_number(rulestring, start)?;
+    let end: u32 = parse_number(rulestring, end)?;
+    ensure!(
+        start <= end && end < Counts::CAPACITY,
+        "invalid rulestring {rulestring:?}: {range:?} is not a valid range of neighbour counts"
+    );
+    (start..=end).for_each(|count| counts.insert(count));
+    Ok(())
+}
+
+fn write_counts(f: &mut fmt::Formatter<'_>, counts: &Counts) -> fmt::Result {
+    (0..=8)
+        .filter(|&count| counts.contains(count))
+        .try_for_each(|count| write!(f, "{count}"))
+}
+
+fn write_ranges(f: &mut fmt::Formatter<'_>, counts: &Counts) -> fmt::Result {
+    for (i, (start, end)) in counts.ranges().into_iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{start}..{end}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counts, Neighbourhood, Rule};
+
+    fn counts(counts: &[u32]) -> Counts {
+        let mut set = Counts::default();
+        counts.iter().for_each(|&count| set.insert(count));
+        set
+    }
+
+    #[test]
+    fn parses_life_like_rules() {
+        let parse = |rulestring: &str| rulestring.parse::<Rule>().unwrap();
+        assert_eq!(parse("B3/S23"), Rule::CONWAY);
+        assert_eq!(parse("b3/s23"), Rule::CONWAY);
+        assert_eq!(parse("B3S23"), Rule::CONWAY);
+        // The legacy S/B order.
+        assert_eq!(parse("23/3"), Rule::CONWAY);
+
+        let highlife = parse("b36/s23");
+        assert_eq!(highlife.birth, counts(&[3, 6]));
+        assert_eq!(highlife.survival, counts(&[2, 3]));
+        assert_eq!(highlife.neighbourhood, Neighbourhood::Moore);
+
+        let seeds = parse("B2/S");
+        assert_eq!(seeds.birth, counts(&[2]));
+        assert_eq!(seeds.survival, Counts::default());
+
+        for (rulestring, displayed) in [
+            ("B3/S23", "B3/S23"),
+            ("b36/s23", "B36/S23"),
+            ("23/3", "B3/S23"),
+            ("B2/S", "B2/S"),
+            ("B/S012345678", "B/S012345678"),
+            ("B2/S34H", "B2/S34H"),
+        ] {
+            let rule = parse(rulestring);
+            assert_eq!(rule.to_string(), displayed, "{rulestring:?}");
+            assert_eq!(parse(displayed), rule, "{rulestring:?}");
+        }
+
+        for invalid in ["B9/S", "B3S23x", "", "B3//S23", "X3/S23", "B3/B23"] {
+            assert!(invalid.parse::<Rule>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn parses_generations_rules() {
+        let parse = |rulestring: &str| rulestring.parse::<Rule>().unwrap();
+        let brians_brain = parse("B2/S/C3");
+        assert_eq!(brians_brain.birth, counts(&[2]));
+        assert_eq!(brians_brain.survival, Counts::default());
+        assert_eq!(brians_brain.states, 3);
+        assert_eq!(parse("/2/3"), brians_brain);
+        assert_eq!(parse("b2/s/g3"), brians_brain);
+
+        let star_wars = parse("345/2/4");
+        assert_eq!(star_wars.birth, counts(&[2]));
+        assert_eq!(star_wars.survival, counts(&[3, 4, 5]));
+        assert_eq!(star_wars.states, 4);
+
+        for (rulestring, displayed) in [
+            ("/2/3", "B2/S/C3"),
+            ("345/2/4", "B2/S345/C4"),
+            ("B2/S34/C6H", "B2/S34/C6H"),
+        ] {
+            let rule = parse(rulestring);
+            assert_eq!(rule.to_string(), displayed, "{rulestring:?}");
+            assert_eq!(parse(displayed), rule, "{rulestring:?}");
+        }
+
+        for invalid in ["B2/S/C1", "B2/S/C0", "/2/1", "B2/S/C257", "B2/S/Cx"] {
+            assert!(invalid.parse::<Rule>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn parses_larger_than_life_rules() {
+        let parse = |rulestring: &str| rulestring.parse::<Rule>().unwrap();
+        let bosco = parse("R5,C0,M1,S34..58,B34..45,NM");
+        assert_eq!(bosco.radius, 5);
+        assert_eq!(bosco.states, 2);
+        assert!(bosco.include_centre);
+        assert_eq!(bosco.neighbourhood, Neighbourhood::Moore);
+        assert_eq!(bosco.birth, counts(&(34..=45).collect::<Vec<_>>()));
+        assert_eq!(bosco.survival, counts(&(34..=58).collect::<Vec<_>>()));
+
+        // Bare ranges extend the field before them, and fields may come in any order or case.
+        let rule = parse("r2, c3, m0, s2..3,5..6, b4, nn");
+        assert_eq!(rule.radius, 2);
+        assert_eq!(rule.states, 3);
+        assert!(!rule.include_centre);
+        assert_eq!(rule.neighbourhood, Neighbourhood::VonNeumann);
+        assert_eq!(rule.birth, counts(&[4]));
+        assert_eq!(rule.survival, counts(&[2, 3, 5, 6]));
+
+        for (rulestring, displayed) in [
+            ("R5,C0,M1,S34..58,B34..45,NM", "R5,C0,M1,S34..58,B34..45,NM"),
+            ("r2,c3,m0,s2..3,5..6,b4,nn", "R2,C3,M0,S2..3,5..6,B4..4,NN"),
+            ("R3,C0,M0,S,B0..2,NM", "R3,C0,M0,S,B0..2,NM"),
+            // Radius 1 Moore rules without the centre are written in B/S notation.
+            ("R1,C0,M0,S2..3,B3,NM", "B3/S23"),
+        ] {
+            let rule = parse(rulestring);
+            assert_eq!(rule.to_string(), displayed, "{rulestring:?}");
+            assert_eq!(parse(displayed), rule, "{rulestring:?}");
+        }
+
+        for invalid in [
+            "R0,C0,M0,S2..3,B3,NM",
+            "R11,C0,M0,S2..3,B3,NM",
+            "R2,C257,M0,S2..3,B3,NM",
+            "R2,C0,M0,S3..2,B3,NM",
+            "R2,C0,M0,S2..512,B3,NM",
+            "R2,C0,M0,S2..3,B3,NX",
+            "R2,,S2..3",
+            "R2,5..6",
+            "R2,Sx",
+            "R2,X3",
+            "R1,é",
+            "R1,Sé",
+            "Ré",
+        ] {
+            assert!(invalid.parse::<Rule>().is_err(), "{invalid:?}");
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Multi-state rules given by an arbitrary transition function of a cell and its neighbours.
+//!
+//! The function is stored as a rule tree, the decision diagram Golly uses: each level of the tree
+//! looks at one cell of the neighbourhood and picks a child by that cell's state, and the last
+//! level, which looks at the centre cell, yields the new state. Identical subtrees are shared, so
+//! rules that only depend on a few of the cells stay small.
+//!
+//! The Moore neighbourhood is visited in the order NW, NE, SW, SE, N, W, E, S and the von Neumann
+//! neighbourhood in the order N, W, E, S, in both cases followed by the centre cell.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleTree {
+    pub name: String,
+    pub states: u32,
+    /// 8 for the Moore neighbourhood, 4 for the von Neumann neighbourhood.
+    pub neighbours: u32,
+    /// The nodes, `states` entries each. Entry `s` of a node is the offset of the node that looks
+    /// at the next cell when the current one is in state `s`, or in the nodes looking at the
+    /// centre cell the new state.
+    pub nodes: Vec<u32>,
+    /// Offset of the node looking at the first cell.
+    pub root: u32,
+    /// RGB colour of each state.
+    pub colours: Vec<[u8; 3]>,
+}
+
+impl RuleTree {
+    /// Tabulates `next`, which maps the states of the neighbourhood in tree order to the new
+    /// state of the centre cell.
+    pub fn from_fn(
+        name: &str,
+        states: u32,
+        neighbours: u32,
+        next: impl Fn(&[u32]) -> u32,
+    ) -> RuleTree {
+        fn build(
+            builder: &mut TreeBuilder,
+            cells: &mut [u32],
+            depth: usize,
+            next: &impl Fn(&[u32]) -> u32,
+        ) -> u32 {
+            let children = (0..builder.states)
+                .map(|state| {
+                    cells[depth] = state;
+                    if depth + 1 == cells.len() {
+                        next(cells)
+                    } else {
+                        build(builder, cells, depth + 1, next)
+                    }
+                })
+                .collect();
+            builder.node(cells.len() - depth, children)
+        }
+
+        let mut builder = TreeBuilder::new(states);
+        let mut cells = vec![0; neighbours as usize + 1];
+        let root = build(&mut builder, &mut cells, 0, &next);
+        builder.finish(name, neighbours, root)
+    }
+
+    /// The new state of the centre cell, given the neighbourhood in tree order.
+    pub fn next(&self, cells: &[u32]) -> u32 {
+        cells.iter().fold(self.root, |node, &state| {
+            self.nodes[(node + state) as usize]
+        })
+    }
+}
+
+/// Builds a rule tree bottom up, storing each distinct node once.
+pub struct TreeBuilder {
+    pub states: u32,
+    nodes: Vec<u32>,
+    offsets: HashMap<(usize, Vec<u32>), u32>,
+}
+
+impl TreeBuilder {
+    pub fn new(states: u32) -> TreeBuilder {
+        TreeBuilder {
+            states,
+            nodes: Vec::new(),
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Adds a node `level` steps above the new states and returns its offset. The children of a
+    /// level 1 node are states, those of higher nodes offsets of nodes one level down.
+    pub fn node(&mut self, level: usize, children: Vec<u32>) -> u32 {
+        debug_assert_eq!(children.len(), self.states as usize);
+        let nodes = &mut self.nodes;
+        *self
+            .offsets
+            .entry((level, children))
+            .or_insert_with_key(|(_, children)| {
+                let offset = nodes.len() as u32;
+                nodes.extend_from_slice(children);
+                offset
+            })
+    }
+
+    pub fn finish(self, name: &str, neighbours: u32, root: u32) -> RuleTree {
+        RuleTree {
+            name: name.to_string(),
+            states: self.states,
+            neighbours,
+            nodes: self.nodes,
+            root,
+            colours: default_colours(self.states),
+        }
+    }
+}
+
+/// Golly's default colours: dark grey for dead cells and a gradient from red to yellow for the
+/// live states.
+pub fn default_colours(states: u32) -> Vec<[u8; 3]> {
+    let live = states.saturating_sub(1).max(1);
+    std::iter::once([48, 48, 48])
+        .chain((0..live).map(|i| {
+            let green = if live > 1 { 255 * i / (live - 1) } else { 0 };
+            [255, green as u8, 0]
+        }))
+        .take(states as usize)
+        .collect()
+}
+
+/// Brian Silverman's Wireworld: electron heads (1) become tails (2), tails become conductor (3),
+/// and a conductor cell becomes a head if one or two of its Moore neighbours are heads.
+pub fn wireworld() -> RuleTree {
+    let mut tree = RuleTree::from_fn("WireWorld", 4, 8, |cells| match cells[8] {
+        0 => 0,
+        1 => 2,
+        2 => 3,
+        _ => match cells[..8].iter().filter(|&&state| state == 1).count() {
+            1 | 2 => 1,
+            _ => 3,
+        },
+    });
+    tree.colours = vec![[48, 48, 48], [0, 128, 255], [255, 255, 255], [255, 128, 0]];
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wireworld;
+    use crate::{cpu::Cpu, render::Renderer, simulation::Simulation, Config};
+
+    #[test]
+    fn wireworld_counts_heads() {
+        let tree = wireworld();
+        // NW, NE, SW, SE, N, W, E, S and the centre.
+        assert_eq!(tree.next(&[0, 0, 0, 0, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(tree.next(&[3, 3, 3, 3, 3, 3, 3, 3, 1]), 2);
+        assert_eq!(tree.next(&[1, 1, 1, 1, 1, 1, 1, 1, 2]), 3);
+        assert_eq!(tree.next(&[0, 0, 0, 0, 0, 1, 0, 0, 3]), 1);
+        assert_eq!(tree.next(&[1, 0, 0, 0, 0, 1, 0, 0, 3]), 1);
+        assert_eq!(tree.next(&[1, 1, 0, 0, 0, 1, 0, 0, 3]), 3);
+        assert_eq!(tree.next(&[0, 0, 0, 0, 0, 2, 0, 0, 3]), 3);
+    }
+
+    #[test]
+    fn electrons_run_along_wires() {
+        let args = [
+            "--grid-size",
+            "24x5",
+            "--boundary",
+            "P",
+            "--rule",
+            "WireWorld",
+        ];
+        let config = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        // A wire along the middle row with an electron heading east: its head, then its tail.
+        let [width, height] = config.grid_size;
+        let mut cells = vec![0; (width * height) as usize];
+        cells[(2 * width + 1) as usize..(3 * width - 1) as usize].fill(3);
+        cells[(2 * width + 2) as usize] = 2;
+        cells[(2 * width + 3) as usize] = 1;
+
+        let cpu = Cpu::new(&config, cells.clone());
+        let renderer = Renderer::fallback(&config).map(|mut renderer| {
+            renderer.set_cells(&cells);
+            renderer
+        });
+        let mut simulations: Vec<Box<dyn Simulation>> = vec![Box::new(cpu)];
+        simulations.extend(renderer.map(|renderer| Box::new(renderer) as Box<dyn Simulation>));
+        for simulation in &mut simulations {
+            let mut expected = cells.clone();
+            for generation in 1..=width {
+                simulation.step(1);
+                // Heads become tails, tails become conductor, and the head moves on until it
+                // runs off the end of the wire.
+                let head = 3 + generation;
+                for x in 1..width - 1 {
+                    expected[(2 * width + x) as usize] = match x {
+                        _ if x == head => 1,
+                        _ if x + 1 == head => 2,
+                        _ => 3,
+                    };
+                }
+                assert_eq!(
+                    simulation.region([0, 0], [width, height]),
+                    expected,
+                    "generation {generation}"
+                );
+            }
+        }
+    }
+}
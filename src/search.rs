@@ -0,0 +1,377 @@
+//! Soup searches in the manner of apgsearch: seeded soups run on the GPU in an unbounded universe
+//! until they settle, the ash left is split into objects, and each object is named by its apgcode
+//! and counted in a census file.
+//!
+//! An apgcode is `xs` and the population for a still life, `xp` and the period for an oscillator
+//! or `xq` and the period for a spaceship, then an underscore and the object in extended Wechsler
+//! format: strips five rows high, each column a digit whose bits are its cells from the top, with
+//! runs of empty columns shortened and strips separated by `z`. Of all the orientations and phases
+//! of an object, the shortest code wins, then the first in ASCII order.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    objects::{normalise, Follower, Motion},
+    render::{connect_headless, Renderer},
+    simulation::Simulation,
+    soup::Soup,
+    Config,
+};
+
+/// Populations compared, one per generation, to decide that a soup has settled: periods up to
+/// half of it show up twice.
+const WINDOW: u64 = 120;
+
+/// Generations run between checks for a soup having settled.
+const STRIDE: u64 = 240;
+
+/// Generations after which a soup that hasn't settled is given up on.
+const MAX_GENERATIONS: u64 = 20_000;
+
+/// Longest period an object is followed for.
+const MAX_PERIOD: u32 = 64;
+
+/// Soups run between saves of the census, so that a search cut short loses little.
+const SAVE_EVERY: u64 = 100;
+
+/// Census entry of a soup that didn't settle.
+const PATHOLOGICAL: &str = "PATHOLOGICAL";
+
+/// Census entry of an object that doesn't repeat within `MAX_PERIOD` generations on its own.
+const UNKNOWN: &str = "zz_UNKNOWN";
+
+/// Runs `soups` soups, seeded from the configured seed on, and adds what they leave to the
+/// census file.
+pub async fn search(config: &Config, soups: u64) -> Result<()> {
+    let (device, queue) = connect_headless(config.fallback_adapter).await?;
+    let mut renderer = Renderer::new(device, queue, config);
+    let path = config.census.clone().unwrap_or_else(|| {
+        let rule = config.rule.to_string().to_lowercase().replace('/', "");
+        PathBuf::from(format!("census-{rule}.txt"))
+    });
+    let mut census = Census::load(&path)?;
+    let mut follower = Follower::new(config, MAX_PERIOD);
+
+    let start = Instant::now();
+    let mut objects = 0;
+    for index in 0..soups {
+        let soup = Soup {
+            seed: config.soup.seed.wrapping_add(index),
+            ..config.soup.clone()
+        };
+        renderer.set_cells(&soup.cells(config.grid_size, 1));
+        census.soups += 1;
+        if settle(&mut renderer) {
+            for code in identify(&mut follower, &renderer.live_cells()) {
+                census.add(&code);
+                objects += 1;
+            }
+        } else {
+            eprintln!(
+                "soup {} is still going after {MAX_GENERATIONS} generations",
+                soup.seed
+            );
+            census.add(PATHOLOGICAL);
+        }
+        if (index + 1) % SAVE_EVERY == 0 {
+            census.save(&path)?;
+        }
+    }
+    census.save(&path)?;
+
+    println!(
+        "{soups} soups, {objects} objects in {:.1?}; {} soups in {}",
+        start.elapsed(),
+        census.soups,
+        path.display()
+    );
+    for (code, count) in census.sorted().into_iter().take(10) {
+        println!("{count:>10} {code}");
+    }
+    Ok(())
+}
+
+/// Runs the universe until its population repeats, or returns false if it is still going after
+/// `MAX_GENERATIONS` generations. The populations are the cell counts of the fingerprints the GPU
+/// gathers, so only they are read back.
+fn settle(renderer: &mut Renderer) -> bool {
+    let mut generations = 0;
+    while generations < MAX_GENERATIONS {
+        renderer.step(WINDOW);
+        let detector = renderer
+            .detector()
+            .expect("searches fingerprint generations");
+        let populations: Vec<u32> = (detector.recent(WINDOW as usize))
+            .map(|(_, fingerprint)| fingerprint.cells)
+            .collect();
+        let window = populations.len();
+        if (1..=window / 2).any(|p| (p..window).all(|i| populations[i] == populations[i - p])) {
+            return true;
+        }
+        renderer.step(STRIDE);
+        generations += WINDOW + STRIDE;
+    }
+    false
+}
+
+/// The apgcodes of the objects the live cells make up.
+fn identify(follower: &mut Follower, cells: &[[i32; 2]]) -> Vec<String> {
+    (follower.identify(cells).into_iter())
+        .map(|(_, motion)| motion.map_or_else(|| UNKNOWN.into(), |motion| apgcode(&motion)))
+        .collect()
+}
+
+/// The apgcode of an object that repeats.
+fn apgcode(motion: &Motion) -> String {
+    let prefix = match motion {
+        _ if motion.is_still_life() => format!("xs{}", motion.phases[0].len()),
+        _ if motion.is_spaceship() => format!("xq{}", motion.period),
+        _ => format!("xp{}", motion.period),
+    };
+    let code = (motion.phases.iter())
+        .flat_map(|phase| orientations(phase))
+        .map(|phase| wechsler(&phase))
+        .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+        .expect("an object has orientations");
+    format!("{prefix}_{code}")
+}
+
+/// The object turned and mirrored in all eight ways.
+fn orientations(cells: &[[i32; 2]]) -> Vec<Vec<[i32; 2]>> {
+    let transforms: [fn([i32; 2]) -> [i32; 2]; 8] = [
+        |[x, y]| [x, y],
+        |[x, y]| [-x, y],
+        |[x, y]| [x, -y],
+        |[x, y]| [-x, -y],
+        |[x, y]| [y, x],
+        |[x, y]| [-y, x],
+        |[x, y]| [y, -x],
+        |[x, y]| [-y, -x],
+    ];
+    (transforms.iter())
+        .map(|transform| {
+            let cells: Vec<_> = cells.iter().map(|&cell| transform(cell)).collect();
+            normalise(&cells).0
+        })
+        .collect()
+}
+
+/// An object in extended Wechsler format, reading its rows from `y = 0` on.
+fn wechsler(cells: &[[i32; 2]]) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let cells: HashSet<[i32; 2]> = cells.iter().copied().collect();
+    let width = cells.iter().map(|&[x, _]| x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|&[_, y]| y + 1).max().unwrap_or(0);
+    let mut strips = Vec::new();
+    for top in (0..height).step_by(5) {
+        let mut columns: Vec<usize> = (0..width)
+            .map(|x| {
+                (0..5)
+                    .filter(|&row| cells.contains(&[x, top + row]))
+                    .map(|row| 1 << row)
+                    .sum()
+            })
+            .collect();
+        while columns.last() == Some(&0) {
+            columns.pop();
+        }
+        let mut strip = String::new();
+        let mut zeros = 0;
+        for column in columns.into_iter().chain([usize::MAX]) {
+            if column == 0 {
+                zeros += 1;
+                continue;
+            }
+            // Runs of 2 and 3 empty columns are `w` and `x`, 4 to 39 `y` and the count less 4.
+            while zeros > 0 {
+                let run = zeros.min(39);
+                match run {
+                    1 => strip.push('0'),
+                    2 => strip.push('w'),
+                    3 => strip.push('x'),
+                    _ => {
+                        strip.push('y');
+                        strip.push(DIGITS[run - 4] as char);
+                    }
+                }
+                zeros -= run;
+            }
+            if column != usize::MAX {
+                strip.push(DIGITS[column] as char);
+            }
+        }
+        strips.push(strip);
+    }
+    strips.join("z")
+}
+
+/// How many times each object turned up, and in how many soups.
+#[derive(Debug, Default, PartialEq)]
+struct Census {
+    soups: u64,
+    counts: BTreeMap<String, u64>,
+}
+
+impl Census {
+    /// Reads a census file, or starts a new census if there is none.
+    fn load(path: &Path) -> Result<Census> {
+        if !path.exists() {
+            return Ok(Census::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Census::parse(&text).with_context(|| format!("invalid census {}", path.display()))
+    }
+
+    /// Parses a census: a `# soups` line and then an apgcode and its count on each line.
+    fn parse(text: &str) -> Result<Census> {
+        let mut census = Census::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let count = |count: &str| {
+                (count.parse::<u64>()).with_context(|| format!("invalid count in {line:?}"))
+            };
+            match line.strip_prefix("# soups ") {
+                Some(soups) => census.soups = count(soups)?,
+                None => {
+                    let (code, n) = line
+                        .split_once(' ')
+                        .context("expected an apgcode and a count")?;
+                    *census.counts.entry(code.to_string()).or_default() += count(n.trim())?;
+                }
+            }
+        }
+        Ok(census)
+    }
+
+    /// Writes the census to `path`. The census is written next to it first and then moved
+    /// over it, so that a crash while writing leaves the last census whole.
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut text = format!("# soups {}\n", self.soups);
+        for (code, count) in self.sorted() {
+            text += &format!("{code} {count}\n");
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        fs::write(&temporary, text)
+            .with_context(|| format!("failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, path).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn add(&mut self, code: &str) {
+        *self.counts.entry(code.to_string()).or_default() += 1;
+    }
+
+    /// The entries, most common first.
+    fn sorted(&self) -> Vec<(&str, u64)> {
+        let mut entries: Vec<_> = (self.counts.iter())
+            .map(|(code, &count)| (code.as_str(), count))
+            .collect();
+        entries.sort_by_key(|&(code, count)| (std::cmp::Reverse(count), code));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apgcode, identify, settle, Census, MAX_PERIOD};
+    use crate::{objects::Follower, render::Renderer, Config};
+
+    /// The live cells of a pattern given as plaintext rows, top row first, with its top left
+    /// corner at `[x, y]`.
+    fn cells([x, y]: [i32; 2], rows: &[&str]) -> Vec<[i32; 2]> {
+        let mut cells = Vec::new();
+        for (row, line) in rows.iter().enumerate() {
+            for (column, cell) in line.chars().enumerate() {
+                if cell == 'O' {
+                    cells.push([x + column as i32, y - row as i32]);
+                }
+            }
+        }
+        cells
+    }
+
+    fn config() -> Config {
+        Config::from_args(["--search", "1"].into_iter().map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn apgcodes_of_common_objects() {
+        let mut follower = Follower::new(&config(), MAX_PERIOD);
+        for (rows, code) in [
+            (&["OO", "OO"][..], "xs4_33"),
+            (&[".O.", "O.O", ".O."], "xs4_252"),
+            (&["OO.", "O.O", ".O."], "xs5_253"),
+            (&[".OO.", "O..O", ".OO."], "xs6_696"),
+            (&["OO.", "O.O", ".OO"], "xs6_356"),
+            (&[".OO.", "O..O", ".O.O", "..O."], "xs7_2596"),
+            (&[".OO.", "O..O", "O..O", ".OO."], "xs8_6996"),
+            (&["OOO"], "xp2_7"),
+            (&[".OOO", "OOO."], "xp2_7e"),
+            (&["OO..", "OO..", "..OO", "..OO"], "xp2_318c"),
+            (&[".O.", "..O", "OOO"], "xq4_153"),
+            (&[".O..O", "O....", "O...O", "OOOO."], "xq4_6frc"),
+        ] {
+            let object = cells([0, 0], rows);
+            let motion = follower.follow(&object);
+            assert_eq!(
+                motion.map(|motion| apgcode(&motion)).as_deref(),
+                Some(code),
+                "{rows:?}"
+            );
+        }
+        // The R-pentomino takes over a thousand generations to settle.
+        assert_eq!(
+            follower.follow(&cells([0, 0], &[".OO", "OO.", ".O."])),
+            None
+        );
+    }
+
+    #[test]
+    fn censuses_add_up() {
+        let mut census = Census::parse("# soups 3\nxs4_33 5\nxp2_7 2\n").unwrap();
+        census.soups += 1;
+        census.add("xp2_7");
+        census.add("xq4_153");
+        let path = std::env::temp_dir().join(format!("census-{}.txt", std::process::id()));
+        std::fs::write(&path, "# soups 1000\n").unwrap();
+        census.save(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "# soups 4\nxs4_33 5\nxp2_7 3\nxq4_153 1\n");
+        // Nothing is left next to it.
+        assert!(!path.with_extension("txt.tmp").exists());
+        assert!(Census::parse("xs4_33 many").is_err());
+    }
+
+    #[test]
+    fn settled_soups_split_into_objects() {
+        // Two blocks a cell apart, a blinker, a beehive, and a glider flying off.
+        let mut live = cells([0, 28], &["OO.OO", "OO.OO"]);
+        live.extend(cells([12, 28], &["OOO"]));
+        live.extend(cells([12, 20], &[".OO.", "O..O", ".OO."]));
+        live.extend(cells([24, 10], &[".O.", "..O", "OOO"]));
+        let config = config();
+        let Some(mut renderer) = Renderer::fallback(&config) else {
+            return;
+        };
+        let [width, height] = config.grid_size;
+        let mut grid = vec![0; (width * height) as usize];
+        for &[x, y] in &live {
+            grid[(y as u32 * width + x as u32) as usize] = 1;
+        }
+        renderer.set_cells(&grid);
+        assert!(settle(&mut renderer));
+        let mut follower = Follower::new(&config, MAX_PERIOD);
+        let mut codes = identify(&mut follower, &renderer.live_cells());
+        codes.sort();
+        assert_eq!(codes, ["xp2_7", "xq4_153", "xs4_33", "xs4_33", "xs6_696"]);
+    }
+}
@@ -1,39 +1,450 @@
-@group(0) @binding(0) var<uniform> grid: vec2f;
+struct Uniforms {
+  grid_size: vec2f,
+  states: u32,         // more than 2 for Generations rules
+  radius: u32,         // neighbourhood radius, above 1 for Larger than Life rules
+  neighbourhood: u32,  // 0: Moore, 1: von Neumann, 2: weighted stencil, 3: hexagonal
+  include_centre: u32, // 1 if the cell itself is part of its neighbourhood
+  stencil_len: u32,
+  isotropic: u32,      // 1 for isotropic non-totalistic rules, which use the transitions table
+  // bit n set: a dead cell with n live neighbours is born / a live cell with n live neighbours survives
+  birth: array<vec4u, 4>,
+  survival: array<vec4u, 4>,
+  topology: u32,       // 0: square, 1: hexagonal with odd rows shifted right,
+                       // 2/3: triangular with vertex/edge neighbours, (x + y) even pointing up
+  tree_neighbours: u32, // 8 or 4 for rules given by a rule tree, 0 otherwise
+  tree_root: u32,
+  // 0: torus, 1: plane, 2/3: Klein bottle with twisted top and bottom/side edges,
+  // 4: cross-surface, 5: sphere, 6: unbounded, computed chunk by chunk with grid_size the chunk size
+  boundary: u32,
+  shift: vec2i,        // x shift across the top and bottom edges, y shift across the sides (torus)
+  reach: u32,          // how far from a live cell the next generation can change
+  cell_storage: u32,   // 0: a state per word, 1: bit x % 32 of word (y * width + x) / 32 is the
+                       // cell at (x, y), 2: a state per texel of the cell textures
+  view: vec4i,
+  active_tiles: u32,   // 1 if only the tiles near last generation's changes are computed
+  fingerprint: u32,    // word of activity where the fingerprint of a generation is gathered
+}
+
+// A chunk of an unbounded universe: the slots of the 3x3 chunks around it, row by row from the
+// bottom left, -1 where all cells are dead; the middle entry is -1 for free slots
+struct Chunk {
+  neighbours: array<i32, 9>,
+  origin: vec2i,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
 @group(0) @binding(1) var<storage> cellStateIn: array<u32>;
 @group(0) @binding(2) var<storage, read_write> cellStateOut: array<u32>;
+// rowSums[y * (width + 1) + x]: number of live cells left of x in row y
+@group(0) @binding(3) var<storage, read_write> rowSums: array<u32>;
+// (dx, dy, weight, _) of each cell in a weighted stencil neighbourhood
+@group(0) @binding(4) var<storage> stencil: array<vec4i>;
+// bit 0: birth, bit 1: survival, indexed by the live neighbours (bit i: N, NE, E, SE, S, SW, W, NW)
+@group(0) @binding(5) var<storage> transitions: array<u32, 256>;
+// Rule tree nodes: entry node + s is the next node when the cell looked at is in state s, or in
+// the last level the new state
+@group(0) @binding(6) var<storage> tree: array<u32>;
+@group(0) @binding(8) var<storage> chunks: array<Chunk>;
+// Per slot, bit 4: the chunk has live cells, other bits (as in Chunk.neighbours): live cells are
+// within reach of that neighbour.
+// With active tiles: the number of active tiles and the workgroups to dispatch for them (x, y, z),
+// then per tile whether it changed, whether it is active, and the list of active tiles.
+// After either, the fingerprint of a generation (see fingerprintBounds).
+@group(0) @binding(9) var<storage, read_write> activity: array<atomic<u32>>;
+// The cells when they are stored in textures, in place of cellStateIn and cellStateOut
+@group(0) @binding(10) var cellTextureIn: texture_2d<u32>;
+@group(0) @binding(11) var cellTextureOut: texture_storage_2d<r32uint, write>;
+
+// The chunk slot being computed in an unbounded universe, 0 otherwise.
+var<private> slot: u32;
+
+// The tiled kernel's workgroups first load their cells and a one cell halo around them into
+// workgroup memory, so that each cell is read from the storage buffer once instead of by each of
+// its neighbours.
+const TILE_SIZE = 8u;
+const TILE_SPAN = TILE_SIZE + 2u;
+const TILE_CELLS = TILE_SPAN * TILE_SPAN;
+var<workgroup> tile: array<u32, TILE_CELLS>;
+var<private> tiled: bool;
+var<private> tileOrigin: vec2i; // position of tile[0]
 
 fn cellActive(x: u32, y: u32) -> u32 {
-return cellStateIn[cellIndex(vec2(x, y))];
+  return activeAt(bitcast<vec2i>(vec2(x, y)));
+}
+
+// Positions outside the grid come in as coordinates that underflowed, e.g. cell.x - 1u.
+fn cellState(x: u32, y: u32) -> u32 {
+  return stateAt(bitcast<vec2i>(vec2(x, y)));
+}
+
+fn activeAt(position: vec2i) -> u32 {
+  return u32(stateAt(position) == 1u); // refractory cells don't count
+}
+
+// The state at a position, from the workgroup's tile if the tiled kernel has loaded it.
+fn stateAt(position: vec2i) -> u32 {
+  if tiled {
+    let t = position - tileOrigin;
+    if all(t >= vec2i(0i)) && all(t < vec2i(i32(TILE_SPAN))) {
+      return tile[u32(t.y) * TILE_SPAN + u32(t.x)];
+    }
+  }
+  let i = boundaryCell(position);
+  if i < 0i {
+    return 0u;
+  }
+  return loadCell(u32(i));
+}
+
+// The state of the cell at cellIndex i.
+fn loadCell(i: u32) -> u32 {
+  if uniforms.cell_storage == 2u {
+    let width = u32(uniforms.grid_size.x);
+    return textureLoad(cellTextureIn, vec2(i % width, i / width), 0).r;
+  }
+  return cellStateIn[i];
 }
 
 fn cellIndex(cell: vec2u) -> u32 {
-return (cell.y % u32(grid.y)) * u32(grid.y) + (cell.x % u32(grid.x));  //to handle edge overflow cases
+  let size = vec2u(uniforms.grid_size);
+  return (slot * size.y + cell.y) * size.x + cell.x;
+}
+
+fn floorDiv(a: i32, b: i32) -> i32 {
+  return select(a / b, (a - b + 1i) / b, a < 0i);
+}
+
+fn modulo(a: i32, b: i32) -> i32 {
+  return a - floorDiv(a, b) * b;
+}
+
+fn wrap(v: i32, size: u32) -> u32 {
+  return u32(modulo(v, i32(size)));
+}
+
+// The index of the cell a position outside the grid stands for under the boundary conditions,
+// or -1 if it is dead.
+fn boundaryCell(position: vec2i) -> i32 {
+  let size = vec2i(uniforms.grid_size);
+  var p = position;
+  switch uniforms.boundary {
+    case 1u: {
+      if any(p < vec2i(0i)) || any(p >= size) {
+        return -1i;
+      }
+    }
+    case 2u, 3u, 4u: {
+      // Each crossing of a twisted pair of edges mirrors the position along them.
+      let crossings = vec2i(floorDiv(p.x, size.x), floorDiv(p.y, size.y));
+      p = vec2i(modulo(p.x, size.x), modulo(p.y, size.y));
+      if uniforms.boundary != 3u && (crossings.y & 1i) != 0i {
+        p.x = size.x - 1i - p.x;
+      }
+      if uniforms.boundary != 2u && (crossings.x & 1i) != 0i {
+        p.y = size.y - 1i - p.y;
+      }
+    }
+    case 5u: {
+      // Crossing the top edge leads in through the left edge and crossing the bottom edge in
+      // through the right one. Positions beyond a corner cross two edges.
+      let n = size.x;
+      for (var i = 0; i < 2; i++) {
+        if p.y >= n {
+          p = vec2i(p.y - n, n - 1i - p.x);
+        } else if p.x < 0i {
+          p = vec2i(n - 1i - p.y, n + p.x);
+        } else if p.y < 0i {
+          p = vec2i(n + p.y, n - 1i - p.x);
+        } else if p.x >= n {
+          p = vec2i(n - 1i - p.y, p.x - n);
+        }
+      }
+    }
+    case 6u: {
+      // Positions beyond the chunk's edges lie in the chunks next to it.
+      let chunk = vec2i(floorDiv(p.x, size.x), floorDiv(p.y, size.y));
+      let neighbour = chunks[slot].neighbours[(chunk.y + 1i) * 3i + chunk.x + 1i];
+      if neighbour < 0i {
+        return -1i;
+      }
+      p -= chunk * size;
+      return (neighbour * size.y + p.y) * size.x + p.x;
+    }
+    default: {
+      if uniforms.shift.x != 0i {
+        p.x += floorDiv(p.y, size.y) * uniforms.shift.x;
+      } else {
+        p.y += floorDiv(p.x, size.x) * uniforms.shift.y;
+      }
+      p = vec2i(modulo(p.x, size.x), modulo(p.y, size.y));
+    }
+  }
+  if any(p < vec2i(0i)) || any(p >= size) {
+    return -1i;
+  }
+  return p.y * size.x + p.x;
+}
+
+// Whether n is in the survival (or else the birth) set of the rule.
+fn inCounts(survival: bool, n: u32) -> bool {
+  if n >= 512u {
+    return false;
+  }
+  var word = uniforms.birth[n / 128u][(n / 32u) % 4u];
+  if survival {
+    word = uniforms.survival[n / 128u][(n / 32u) % 4u];
+  }
+  return ((word >> (n % 32u)) & 1u) == 1u;
+}
+
+// Live cells in row y between x0 and x1 (inclusive), which may lie outside the grid.
+fn rowRange(y: i32, x0: i32, x1: i32) -> u32 {
+  let width = u32(uniforms.grid_size.x);
+  let row = wrap(y, u32(uniforms.grid_size.y)) * (width + 1u);
+  let a = wrap(x0, width);
+  let b = wrap(x1, width);
+  if a <= b {
+    return rowSums[row + b + 1u] - rowSums[row + a];
+  }
+  return rowSums[row + width] - rowSums[row + a] + rowSums[row + b + 1u];
+}
+
+// Live cells within the Moore or von Neumann neighbourhood of the given radius, centre included.
+// The row prefix sums only hold on a plain torus.
+fn rangeCount(cell: vec2u) -> u32 {
+  let r = i32(uniforms.radius);
+  let x = i32(cell.x);
+  let prefixSums = uniforms.boundary == 0u && all(uniforms.shift == vec2i(0i));
+  var count = 0u;
+  for (var dy = -r; dy <= r; dy++) {
+    let half = select(r, r - abs(dy), uniforms.neighbourhood == 1u);
+    let y = i32(cell.y) + dy;
+    if prefixSums {
+      count += rowRange(y, x - half, x + half);
+    } else {
+      for (var dx = -half; dx <= half; dx++) {
+        count += activeAt(vec2i(x + dx, y));
+      }
+    }
+  }
+  return count;
+}
+
+// Weighted sum of the live cells in the stencil; negative sums match no count.
+fn stencilCount(cell: vec2u) -> u32 {
+  var count = 0i;
+  for (var k = 0u; k < uniforms.stencil_len; k++) {
+    let entry = stencil[k];
+    count += entry.z * i32(activeAt(vec2i(cell.xy) + entry.xy));
+  }
+  return select(u32(count), 0xffffffffu, count < 0i);
+}
+
+// The live Moore neighbours as a bitmask, starting at N and going clockwise.
+fn configuration(cell: vec2u) -> u32 {
+  return cellActive(cell.x, cell.y + 1u) |
+    cellActive(cell.x + 1u, cell.y + 1u) << 1u |
+    cellActive(cell.x + 1u, cell.y) << 2u |
+    cellActive(cell.x + 1u, cell.y - 1u) << 3u |
+    cellActive(cell.x, cell.y - 1u) << 4u |
+    cellActive(cell.x - 1u, cell.y - 1u) << 5u |
+    cellActive(cell.x - 1u, cell.y) << 6u |
+    cellActive(cell.x - 1u, cell.y + 1u) << 7u;
+}
+
+// The six neighbours of a hexagonal cell. On the hexagonal lattice the diagonal neighbours depend
+// on whether the row is shifted; on the square grid the lattice is emulated by skewing it.
+fn hexCount(cell: vec2u) -> u32 {
+  var left = cell.x - 1u; // diagonal neighbours in the rows above and below: left and left + 1
+  if uniforms.topology == 1u && cell.y % 2u == 1u {
+    left = cell.x;
+  }
+  let up = cellActive(left, cell.y + 1u) + cellActive(left + 1u, cell.y + 1u);
+  let side = cellActive(cell.x - 1u, cell.y) + cellActive(cell.x + 1u, cell.y);
+  if uniforms.topology == 1u {
+    return up + side + cellActive(left, cell.y - 1u) + cellActive(left + 1u, cell.y - 1u);
+  }
+  // Golly's emulation: NW, N, W, E, S, SE.
+  return up + side + cellActive(cell.x, cell.y - 1u) + cellActive(cell.x + 1u, cell.y - 1u);
+}
+
+// The neighbours of a triangle. A triangle pointing up shares its base with the one below it, so
+// its corners touch five cells in the row below and three above; pointing down it is the reverse.
+fn triangleCount(cell: vec2u) -> u32 {
+  let side = cellActive(cell.x - 1u, cell.y) + cellActive(cell.x + 1u, cell.y);
+  let up = (cell.x + cell.y) % 2u == 0u;
+  let base = select(cell.y + 1u, cell.y - 1u, up);
+  let apex = select(cell.y - 1u, cell.y + 1u, up);
+  if uniforms.topology == 3u {
+    return side + cellActive(cell.x, base);
+  }
+  var count = side + cellActive(cell.x - 2u, cell.y) + cellActive(cell.x + 2u, cell.y);
+  for (var dx = 0u; dx < 5u; dx++) {
+    count += cellActive(cell.x + dx - 2u, base);
+  }
+  for (var dx = 0u; dx < 3u; dx++) {
+    count += cellActive(cell.x + dx - 1u, apex);
+  }
+  return count;
+}
+
+// Walks the rule tree from the root: NW, NE, SW, SE, N, W, E, S (or only N, W, E, S), then the centre.
+fn treeNext(cell: vec2u) -> u32 {
+  let x = cell.x;
+  let y = cell.y;
+  var node = uniforms.tree_root;
+  if uniforms.tree_neighbours == 8u {
+    node = tree[node + cellState(x - 1u, y + 1u)];
+    node = tree[node + cellState(x + 1u, y + 1u)];
+    node = tree[node + cellState(x - 1u, y - 1u)];
+    node = tree[node + cellState(x + 1u, y - 1u)];
+  }
+  node = tree[node + cellState(x, y + 1u)];
+  node = tree[node + cellState(x - 1u, y)];
+  node = tree[node + cellState(x + 1u, y)];
+  node = tree[node + cellState(x, y - 1u)];
+  return tree[node + cellState(x, y)];
+}
+
+// One invocation per row: exclusive prefix sums of the live cells in that row.
+@compute
+@workgroup_size(64)
+fn rowPrefix(@builtin(global_invocation_id) id: vec3u) {
+  let width = u32(uniforms.grid_size.x);
+  let y = id.x;
+  if y >= u32(uniforms.grid_size.y) {
+    return;
+  }
+  let row = y * (width + 1u);
+  var sum = 0u;
+  rowSums[row] = 0u;
+  for (var x = 0u; x < width; x++) {
+    sum += cellActive(x, y);
+    rowSums[row + x + 1u] = sum;
+  }
+}
+
+// Writes the new state of a cell, and in an unbounded universe records which chunks its life
+// touches.
+fn store(cell: vec2u, i: u32, state: u32) {
+  if uniforms.active_tiles == 1u && state != loadCell(i) {
+    atomicStore(&activity[CHANGED + tileOf(cell)], 1u);
+  }
+  if uniforms.cell_storage == 2u {
+    textureStore(cellTextureOut, cell, vec4u(state));
+  } else {
+    cellStateOut[i] = state;
+  }
+  if uniforms.boundary != 6u || state == 0u {
+    return;
+  }
+  let size = vec2u(uniforms.grid_size);
+  let low = cell < vec2u(uniforms.reach);
+  let high = cell >= size - vec2u(uniforms.reach);
+  let side = select(select(vec2i(0i), vec2i(1i), high), vec2i(-1i), low);
+  var bits = 1u << 4u;
+  bits |= 1u << u32(4i + side.x);
+  bits |= 1u << u32(4i + side.y * 3i);
+  bits |= 1u << u32(4i + side.y * 3i + side.x);
+  atomicOr(&activity[slot], bits);
 }
 
 @compute
 @workgroup_size(8,8) // New line
 fn computeMain(@builtin(global_invocation_id) cell: vec3u) {
-let activeNeighbors = cellActive(cell.x + 1u, cell.y+ 1u) +
-  cellActive(cell.x+1u, cell.y) +
-  cellActive(cell.x+1u, cell.y- 1u) +
-  cellActive(cell.x, cell.y- 1u) +
-  cellActive(cell.x- 1u, cell.y- 1u) +
-  cellActive(cell.x- 1u, cell.y) +
-  cellActive(cell.x- 1u, cell.y+ 1u) +
-  cellActive(cell.x, cell.y+1u);
+if any(cell.xy >= vec2u(uniforms.grid_size)) {
+  return;
+}
+slot = cell.z;
+if uniforms.boundary == 6u && chunks[slot].neighbours[4] < 0i {
+  return;
+}
+evolve(cell.xy);
+}
 
-let i = cellIndex(cell.xy);
+@compute
+@workgroup_size(TILE_SIZE, TILE_SIZE)
+fn tiledMain(
+  @builtin(global_invocation_id) cell: vec3u,
+  @builtin(local_invocation_index) index: u32,
+  @builtin(workgroup_id) group: vec3u,
+) {
+  slot = cell.z;
+  tileOrigin = vec2i(group.xy * TILE_SIZE) - vec2i(1i);
+  for (var i = index; i < TILE_CELLS; i += TILE_SIZE * TILE_SIZE) {
+    tile[i] = stateAt(tileOrigin + vec2i(vec2(i % TILE_SPAN, i / TILE_SPAN)));
+  }
+  workgroupBarrier();
+  tiled = true;
+  if any(cell.xy >= vec2u(uniforms.grid_size)) {
+    return;
+  }
+  if uniforms.boundary == 6u && chunks[slot].neighbours[4] < 0i {
+    return;
+  }
+  evolve(cell.xy);
+}
+
+// Computes the next state of a cell.
+fn evolve(cell: vec2u) {
+let i = cellIndex(cell);
+let state = loadCell(i);
+if uniforms.tree_neighbours != 0u {
+  store(cell, i, treeNext(cell));
+  return;
+}
 
-// Conway's game of life rules:
-switch activeNeighbors {
-  case 2u: { // Active cells with 2 neighbors stay active.
-    cellStateOut[i] = cellStateIn[i];
+var activeNeighbors = 0u;
+if uniforms.topology >= 2u {
+  activeNeighbors = triangleCount(cell);
+} else if uniforms.topology == 1u || uniforms.neighbourhood == 3u {
+  activeNeighbors = hexCount(cell);
+} else if uniforms.neighbourhood == 2u {
+  activeNeighbors = stencilCount(cell);
+} else if uniforms.radius > 1u {
+  activeNeighbors = rangeCount(cell) - u32(state == 1u);
+} else if uniforms.neighbourhood == 1u {
+  // von Neumann: only the four orthogonal neighbours
+  activeNeighbors = cellActive(cell.x+1u, cell.y) +
+    cellActive(cell.x, cell.y- 1u) +
+    cellActive(cell.x- 1u, cell.y) +
+    cellActive(cell.x, cell.y+1u);
+} else {
+  activeNeighbors = cellActive(cell.x + 1u, cell.y+ 1u) +
+    cellActive(cell.x+1u, cell.y) +
+    cellActive(cell.x+1u, cell.y- 1u) +
+    cellActive(cell.x, cell.y- 1u) +
+    cellActive(cell.x- 1u, cell.y- 1u) +
+    cellActive(cell.x- 1u, cell.y) +
+    cellActive(cell.x- 1u, cell.y+ 1u) +
+    cellActive(cell.x, cell.y+1u);
+}
+if uniforms.include_centre == 1u {
+  activeNeighbors += u32(state == 1u);
+}
+
+// Look the neighbour count (or configuration) up in the birth or survival set.
+// Generations rules send live cells that don't survive into the refractory states 2..states-1.
+var born = false;
+var survives = false;
+if uniforms.isotropic == 1u {
+  let transition = transitions[configuration(cell)];
+  born = (transition & 1u) == 1u;
+  survives = (transition & 2u) == 2u;
+} else {
+  born = inCounts(false, activeNeighbors);
+  survives = inCounts(true, activeNeighbors);
+}
+switch state {
+  case 0u: {
+    store(cell, i, u32(born));
   }
-  case 3u: { // Cells with 3 neighbors become or stay active.
-    cellStateOut[i] = 1u;
+  case 1u: {
+    let dying = select(0u, 2u, uniforms.states > 2u);
+    store(cell, i, select(dying, 1u, survives));
   }
-  default: { // Cells with < 2 or > 3 neighbors become inactive.
-    cellStateOut[i] = 0u;
+  default: {
+    store(cell, i, select(state + 1u, 0u, state + 1u >= uniforms.states));
   }
 }
     // if cellStateIn[i]==1u{
@@ -41,4 +452,218 @@ switch activeNeighbors {
     // }else{
     //     cellStateOut[i]=1u;
     // }
-}
\ No newline at end of file
+}
+
+// A word of packed cells, wrapping around a torus; words beyond the edges of a plane are empty.
+fn packedWord(column: i32, row: i32) -> u32 {
+  let columns = i32(uniforms.grid_size.x) / 32i;
+  let rows = i32(uniforms.grid_size.y);
+  if uniforms.boundary == 1u && (column < 0i || column >= columns || row < 0i || row >= rows) {
+    return 0u;
+  }
+  return cellStateIn[modulo(row, rows) * columns + modulo(column, columns)];
+}
+
+// Adds three bits in each of 32 lanes: (sum, carry).
+fn fullAdd(a: u32, b: u32, c: u32) -> vec2u {
+  let ab = a ^ b;
+  return vec2u(ab ^ c, (a & b) | (c & ab));
+}
+
+// One invocation per word of packed cells: the eight neighbours of all 32 cells are added as bit
+// planes by a tree of full adders, and each neighbour count in the birth or survival set is
+// matched against the planes.
+@compute
+@workgroup_size(8, 8)
+fn packedMain(@builtin(global_invocation_id) id: vec3u) {
+  let columns = u32(uniforms.grid_size.x) / 32u;
+  if id.x >= columns || id.y >= u32(uniforms.grid_size.y) {
+    return;
+  }
+  let column = i32(id.x);
+  let row = i32(id.y);
+
+  // The rows above, at and below the word, each shifted so that a cell's bit lines up with its
+  // west, own and east neighbours; bit 0 is the westmost cell of the word.
+  var west: array<u32, 3>;
+  var middle: array<u32, 3>;
+  var east: array<u32, 3>;
+  for (var i = 0; i < 3; i++) {
+    let y = row + 1i - i;
+    let word = packedWord(column, y);
+    west[i] = (word << 1u) | (packedWord(column - 1i, y) >> 31u);
+    middle[i] = word;
+    east[i] = (word >> 1u) | (packedWord(column + 1i, y) << 31u);
+  }
+  let centre = middle[1];
+
+  let a = fullAdd(west[0], middle[0], east[0]);
+  let b = fullAdd(west[2], middle[2], east[2]);
+  let c = vec2u(west[1] ^ east[1], west[1] & east[1]);
+  let ones = fullAdd(a.x, b.x, c.x);
+  let twos = fullAdd(a.y, b.y, c.y);
+  let carry = twos.x & ones.y;
+  var count = array<u32, 4>(ones.x, twos.x ^ ones.y, twos.y ^ carry, twos.y & carry);
+
+  var next = 0u;
+  for (var n = 0u; n <= 8u; n++) {
+    let born = inCounts(false, n);
+    let survives = inCounts(true, n);
+    if !born && !survives {
+      continue;
+    }
+    var equal = 0xffffffffu;
+    for (var bit = 0u; bit < 4u; bit++) {
+      equal &= select(~count[bit], count[bit], ((n >> bit) & 1u) == 1u);
+    }
+    next |= equal & (select(0u, ~centre, born) | select(0u, centre, survives));
+  }
+  cellStateOut[id.y * columns + id.x] = next;
+}
+
+// Active tiles: the grid is cut into TILE_SIZE x TILE_SIZE tiles, and each generation only the
+// tiles within reach of a cell that changed in the last one are computed. The others stay as they
+// are, which the cell buffer written two generations ago already holds.
+const ACTIVE_COUNT = 0u;
+const DISPATCH = 1u;
+const CHANGED = 4u;
+
+fn tileCount() -> vec2u {
+  return (vec2u(uniforms.grid_size) + TILE_SIZE - 1u) / TILE_SIZE;
+}
+
+fn tileOf(cell: vec2u) -> u32 {
+  let tile = cell / TILE_SIZE;
+  return tile.y * tileCount().x + tile.x;
+}
+
+// Lists the tiles within reach of each tile that changed, once each.
+@compute
+@workgroup_size(64)
+fn markTiles(@builtin(global_invocation_id) id: vec3u) {
+  let count = tileCount();
+  let tiles = count.x * count.y;
+  let t = id.x;
+  if t >= tiles || atomicLoad(&activity[CHANGED + t]) == 0u {
+    return;
+  }
+  // Triangles reach two cells sideways.
+  let reach = i32(max(uniforms.reach, select(1u, 2u, uniforms.topology >= 2u)));
+  let size = vec2i(uniforms.grid_size);
+  let origin = vec2i(vec2(t % count.x, t / count.x) * TILE_SIZE);
+  for (var y = origin.y - reach; y < origin.y + i32(TILE_SIZE) + reach; y++) {
+    for (var x = origin.x - reach; x < origin.x + i32(TILE_SIZE) + reach; x++) {
+      let i = boundaryCell(vec2(x, y));
+      if i < 0i {
+        continue;
+      }
+      let u = tileOf(vec2(u32(i) % u32(size.x), u32(i) / u32(size.x)));
+      if atomicExchange(&activity[CHANGED + tiles + u], 1u) == 0u {
+        atomicStore(&activity[CHANGED + 2u * tiles + atomicAdd(&activity[ACTIVE_COUNT], 1u)], u);
+      }
+    }
+  }
+}
+
+// Spreads the active tiles over rows of as many workgroups as a dispatch can have.
+const MAX_DISPATCH = 65535u;
+
+@compute
+@workgroup_size(1)
+fn activeDispatch() {
+  let count = atomicLoad(&activity[ACTIVE_COUNT]);
+  atomicStore(&activity[DISPATCH], min(count, MAX_DISPATCH));
+  atomicStore(&activity[DISPATCH + 1u], (count + MAX_DISPATCH - 1u) / MAX_DISPATCH);
+  atomicStore(&activity[DISPATCH + 2u], 1u);
+}
+
+@compute
+@workgroup_size(TILE_SIZE, TILE_SIZE)
+fn activeMain(
+  @builtin(local_invocation_id) local: vec3u,
+  @builtin(workgroup_id) group: vec3u,
+) {
+  let count = tileCount();
+  let tiles = count.x * count.y;
+  let k = group.y * MAX_DISPATCH + group.x;
+  if k >= atomicLoad(&activity[ACTIVE_COUNT]) {
+    return;
+  }
+  let t = atomicLoad(&activity[CHANGED + 2u * tiles + k]);
+  let cell = vec2(t % count.x, t / count.x) * TILE_SIZE + local.xy;
+  if any(cell >= vec2u(uniforms.grid_size)) {
+    return;
+  }
+  evolve(cell);
+}
+
+
+// The fingerprint of the generation in cellStateIn, gathered in activity from
+// uniforms.fingerprint on by two passes over the cells. The first finds the bottom left corner of
+// the bounding box of the cells that aren't dead, each coordinate with its sign bit flipped and
+// inverted so that atomicMax over the cleared words finds the smallest, and counts them; the
+// second adds up two hashes of each cell's state and position from the corner, which don't
+// depend on where the pattern is or on the order the cells are visited in.
+
+// lowbias32, a 32-bit integer hash.
+fn hash32(value: u32) -> u32 {
+  var x = value;
+  x ^= x >> 16u;
+  x *= 0x7feb352du;
+  x ^= x >> 15u;
+  x *= 0x846ca68bu;
+  return x ^ (x >> 16u);
+}
+
+// The state of the cell a fingerprint invocation visits, 0 outside the grid and in free slots.
+fn visitedState(id: vec3u) -> u32 {
+  if any(id.xy >= vec2u(uniforms.grid_size)) {
+    return 0u;
+  }
+  slot = id.z;
+  if uniforms.boundary == 6u && chunks[slot].neighbours[4] < 0i {
+    return 0u;
+  }
+  if uniforms.cell_storage == 1u {
+    let i = id.y * u32(uniforms.grid_size.x) + id.x;
+    return (cellStateIn[i / 32u] >> (i % 32u)) & 1u;
+  }
+  return loadCell(cellIndex(id.xy));
+}
+
+// The position of the cell a fingerprint invocation visits, in the universe.
+fn visitedPosition(id: vec3u) -> vec2i {
+  if uniforms.boundary == 6u {
+    return chunks[id.z].origin + vec2i(id.xy);
+  }
+  return vec2i(id.xy);
+}
+
+@compute
+@workgroup_size(8, 8)
+fn fingerprintBounds(@builtin(global_invocation_id) id: vec3u) {
+  if visitedState(id) == 0u {
+    return;
+  }
+  let corner = ~(bitcast<vec2u>(visitedPosition(id)) ^ vec2u(0x80000000u));
+  let f = uniforms.fingerprint;
+  atomicMax(&activity[f], corner.x);
+  atomicMax(&activity[f + 1u], corner.y);
+  atomicAdd(&activity[f + 2u], 1u);
+}
+
+@compute
+@workgroup_size(8, 8)
+fn fingerprintCells(@builtin(global_invocation_id) id: vec3u) {
+  let state = visitedState(id);
+  if state == 0u {
+    return;
+  }
+  let f = uniforms.fingerprint;
+  let words = vec2(atomicLoad(&activity[f]), atomicLoad(&activity[f + 1u]));
+  let corner = bitcast<vec2i>(~words ^ vec2u(0x80000000u));
+  let p = bitcast<vec2u>(visitedPosition(id) - corner);
+  let key = hash32(p.x ^ hash32(p.y ^ hash32(state)));
+  atomicAdd(&activity[f + 3u], key);
+  atomicAdd(&activity[f + 4u], hash32(key ^ 0x9e3779b9u));
+}
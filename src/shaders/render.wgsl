@@ -9,31 +9,123 @@ var<private> vertices: TriangleVertices = TriangleVertices(
 );
 
 struct Uniforms {
-  grid_size:vec2f
+  grid_size:vec2f,
+  states:u32,
+  radius:u32,
+  neighbourhood:u32,
+  include_centre:u32,
+  stencil_len:u32,
+  isotropic:u32,
+  birth:array<vec4u, 4>,
+  survival:array<vec4u, 4>,
+  topology:u32,
+  tree_neighbours:u32,
+  tree_root:u32,
+  boundary:u32,
+  shift:vec2i,
+  reach:u32,
+  cell_storage:u32, // 0: a state per word, 1: packed 32 to a word, 2: in the cell textures
+  view:vec4i, // bottom left cell, width and height shown of an unbounded universe
+  active_tiles:u32,
+  fingerprint:u32,
+}
+
+struct Chunk {
+  neighbours:array<i32, 9>,
+  origin:vec2i,
 }
 
 @group(0) @binding(1) var<storage> cellState: array<u32>;
+// Colour of each state of a rule tree
+@group(0) @binding(7) var<storage> palette: array<vec4f>;
+// The chunks of an unbounded universe, by slot
+@group(0) @binding(8) var<storage> chunks: array<Chunk>;
+// The cells when they are stored in textures
+@group(0) @binding(10) var cellTexture: texture_2d<u32>;
 
 struct VertexOutput {
   @builtin(position) pos: vec4f,
   @location(0) cell: vec2f, // New line!
+  @location(1) @interpolate(flat) state: u32,
 };
 
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
 
 
+const SQRT_3: f32 = 1.7320508;
+
+// Pointy-top hexagons drawn as a fan of six triangles; odd rows are shifted right by half a cell.
+fn hexagonVertex(cell: vec2f, vid: u32, scale: f32) -> vec2f {
+  let grid = uniforms.grid_size;
+  // Size of the lattice in units of the hexagon's circumradius.
+  let extent = vec2f((grid.x + 0.5) * SQRT_3, 1.5 * grid.y + 0.5);
+  let size = 2. / max(extent.x, extent.y);
+  let shift = 0.5 * f32(u32(cell.y) % 2u);
+  let centre = vec2f((cell.x + shift + 0.5) * SQRT_3, 1.5 * cell.y + 1.);
+
+  var corner = vec2f(0.);
+  let k = vid % 3u;
+  if k != 0u {
+    let angle = radians(60. * f32(vid / 3u + k - 1u) + 30.);
+    corner = vec2f(cos(angle), sin(angle)) * scale * .9;
+  }
+  return (centre + corner - extent / 2.) * size;
+}
+
+// Triangles of side 1 in rows of height sqrt(3)/2; the cell at (x, y) points up when x + y is even.
+fn triangleVertex(cell: vec2f, vid: u32, scale: f32) -> vec2f {
+  let grid = uniforms.grid_size;
+  let height = SQRT_3 / 2.;
+  let extent = vec2f(grid.x / 2. + .5, grid.y * height);
+  let size = 2. / max(extent.x, extent.y);
+  let up = (u32(cell.x) + u32(cell.y)) % 2u == 0u;
+  // Base corners first, then the apex.
+  let base = select(cell.y + 1., cell.y, up) * height;
+  let apex = select(cell.y, cell.y + 1., up) * height;
+  var corners = array<vec2f, 3>(
+    vec2f(cell.x / 2., base),
+    vec2f(cell.x / 2. + 1., base),
+    vec2f(cell.x / 2. + .5, apex),
+  );
+  let centre = (corners[0] + corners[1] + corners[2]) / 3.;
+  let corner = centre + (corners[vid] - centre) * scale * .8;
+  return (corner - extent / 2.) * size;
+}
+
 @vertex fn display_vs(@builtin(vertex_index) vid: u32,@builtin(instance_index) instance: u32) ->VertexOutput {
-  let i = f32(instance);
-  let cell = vec2f(i%uniforms.grid_size.x, floor(i/uniforms.grid_size.y)); // Cell(1,1) in the image above
-  let cellOffset = cell / uniforms.grid_size * 2.; // Compute the offset to cell
-  
-  let state = f32(cellState[instance]);
-  let gridPos = (vertices[vid]*state*.8+1.)/uniforms.grid_size- 1.0 + cellOffset;
+  var grid = uniforms.grid_size;
+  var cell = vec2f(vec2(instance % u32(grid.x), instance / u32(grid.x))); // Cell(1,1) in the image above
+  if uniforms.boundary == 6u {
+    // Instances run through the cells of each chunk in view, grid_size being the chunk size.
+    let size = u32(grid.x);
+    let slot = instance / (size * size);
+    let local = vec2i(vec2(instance % size, (instance / size) % size));
+    cell = vec2f(chunks[slot].origin + local - uniforms.view.xy);
+    grid = vec2f(uniforms.view.zw);
+  }
+
+  var state = 0u;
+  if uniforms.cell_storage == 1u {
+    state = (cellState[instance / 32u] >> (instance % 32u)) & 1u;
+  } else {
+    state = cellState[instance];
+  }
+  let scale = f32(state != 0u);
+  var gridPos = vec2f(0.);
+  if uniforms.topology == 1u {
+    gridPos = hexagonVertex(cell, vid, scale);
+  } else if uniforms.topology >= 2u {
+    gridPos = triangleVertex(cell, vid, scale);
+  } else {
+    // Square cells, with the longer side of the grid filling the window.
+    gridPos = (cell + .5 + vertices[vid]*scale*.4 - grid/2.) * 2./max(grid.x, grid.y);
+  }
 
 
   var output: VertexOutput;
   output.pos = vec4f(gridPos, 0.0, 1.0);
-  output.cell = cell; 
+  output.cell = cell / grid;
+  output.state = state;
   return output;
 }
 
@@ -41,6 +133,65 @@ struct VertexOutput {
 
 @fragment
 fn display_fs(input: VertexOutput) -> @location(0) vec4f {
-  let c = input.cell /uniforms.grid_size;
-  return vec4f(c, 1.-c.x, 1.);
-}
\ No newline at end of file
+  return colour(input.cell, input.state);
+}
+
+// The colour of a live cell, c being its position as a fraction of the grid.
+fn colour(c: vec2f, state: u32) -> vec4f {
+  let alive = vec3f(c, 1.-c.x);
+  if uniforms.tree_neighbours != 0u {
+    return palette[state];
+  }
+  if state <= 1u {
+    return vec4f(alive, 1.);
+  }
+  // Refractory states of Generations rules fade from orange towards the background.
+  let age = f32(state - 1u) / f32(uniforms.states - 1u);
+  return vec4f(mix(vec3f(1., .6, .1), vec3f(.1, .05, .4), age), 1.);
+}
+
+// Cells stored in textures are drawn as one quad over the grid, each pixel reading the texel of
+// the cell under it.
+@vertex fn texture_vs(@builtin(vertex_index) vid: u32) -> VertexOutput {
+  let grid = uniforms.grid_size;
+  let corner = (vertices[vid] + 1.) / 2. * grid;
+  var output: VertexOutput;
+  output.pos = vec4f((corner - grid/2.) * 2./max(grid.x, grid.y), 0.0, 1.0);
+  output.cell = corner;
+  return output;
+}
+
+@fragment
+fn texture_fs(input: VertexOutput) -> @location(0) vec4f {
+  let cell = min(vec2u(input.cell), vec2u(uniforms.grid_size) - 1u);
+  let state = textureLoad(cellTexture, cell, 0).r;
+  if state == 0u {
+    return vec4f(0., 0., .4, 1.); // the background
+  }
+  return colour(vec2f(cell) / uniforms.grid_size, state);
+}
+// Rectangles drawn over the cells, such as the boxes and labels of the objects in view, in cells
+// from the bottom left corner of the view.
+struct OverlayOutput {
+  @builtin(position) pos: vec4f,
+  @location(0) colour: vec4f,
+};
+
+@vertex fn overlay_vs(
+  @builtin(vertex_index) vid: u32,
+  @location(0) corners: vec4f,
+  @location(1) colour: vec4f,
+) -> OverlayOutput {
+  // The view is the whole of a bounded grid.
+  let grid = vec2f(uniforms.view.zw);
+  let corner = mix(corners.xy, corners.zw, (vertices[vid] + 1.) / 2.);
+  var output: OverlayOutput;
+  output.pos = vec4f((corner - grid/2.) * 2./max(grid.x, grid.y), 0.0, 1.0);
+  output.colour = colour;
+  return output;
+}
+
+@fragment
+fn overlay_fs(input: OverlayOutput) -> @location(0) vec4f {
+  return input.colour;
+}
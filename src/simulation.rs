@@ -0,0 +1,21 @@
+//! What every engine offers, whether it runs on the GPU or the CPU.
+
+use crate::rule::Rule;
+
+pub trait Simulation {
+    /// Switches to `rule` from the next generation on.
+    fn set_rule(&mut self, rule: &Rule);
+
+    /// Advances the universe by `generations` generations.
+    fn step(&mut self, generations: u64);
+
+    /// Number of generations run so far.
+    fn generation(&self) -> u64;
+
+    /// Number of live cells, not counting refractory ones.
+    fn population(&self) -> u64;
+
+    /// The states of the `[width, height]` cells whose bottom left corner is at `origin`, row by
+    /// row from the bottom.
+    fn region(&self, origin: [i64; 2], size: [u32; 2]) -> Vec<u32>;
+}
@@ -0,0 +1,293 @@
+//! Seeded random soups: a square or rectangle of random cells somewhere in an empty grid, possibly
+//! symmetric like apgsearch's soups. The same seed gives the same soup on every machine.
+
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Result};
+
+/// SplitMix64, a generator small enough to spell out here, so that a seed means the same soup
+/// whatever the platform or the version of the `rand` crate.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// True with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64) < p * (1u64 << 53) as f64
+    }
+
+    /// A number below `n`.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (((self.next_u64() >> 32) * u64::from(n)) >> 32) as u32
+    }
+}
+
+/// The symmetry of a soup, named as in apgsearch.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Symmetry {
+    /// No symmetry.
+    #[default]
+    C1,
+    /// Unchanged by a half turn.
+    C2,
+    /// Unchanged by a quarter turn; the soup must be square.
+    C4,
+    /// Mirrored left to right.
+    D2,
+    /// Mirrored left to right and top to bottom.
+    D4,
+    /// Unchanged by quarter turns and mirroring; the soup must be square.
+    D8,
+}
+
+impl Symmetry {
+    /// The positions a position in a `[width, height]` soup is mapped to, itself included.
+    fn orbit(self, [width, height]: [u32; 2], [x, y]: [u32; 2]) -> Vec<[u32; 2]> {
+        let [right, top] = [width - 1, height - 1];
+        let half_turn = [right - x, top - y];
+        match self {
+            Symmetry::C1 => vec![[x, y]],
+            Symmetry::C2 => vec![[x, y], half_turn],
+            Symmetry::C4 => vec![[x, y], [top - y, x], half_turn, [y, right - x]],
+            Symmetry::D2 => vec![[x, y], [right - x, y]],
+            Symmetry::D4 => vec![[x, y], [right - x, y], [x, top - y], half_turn],
+            Symmetry::D8 => vec![
+                [x, y],
+                [top - y, x],
+                half_turn,
+                [y, right - x],
+                [right - x, y],
+                [x, top - y],
+                [y, x],
+                [top - y, right - x],
+            ],
+        }
+    }
+
+    /// Whether the symmetry turns the soup by a quarter, which only a square soup survives.
+    fn needs_square(self) -> bool {
+        matches!(self, Symmetry::C4 | Symmetry::D8)
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = anyhow::Error;
+
+    fn from_str(symmetry: &str) -> Result<Symmetry> {
+        Ok(match symmetry.to_ascii_uppercase().as_str() {
+            "C1" => Symmetry::C1,
+            "C2" => Symmetry::C2,
+            "C4" => Symmetry::C4,
+            "D2" => Symmetry::D2,
+            "D4" => Symmetry::D4,
+            "D8" => Symmetry::D8,
+            _ => bail!("unknown symmetry {symmetry:?}, expected C1, C2, C4, D2, D4 or D8"),
+        })
+    }
+}
+
+/// How to fill the grid at the start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Soup {
+    pub seed: u64,
+    /// Chance of each cell of the soup starting alive.
+    pub density: f64,
+    /// Size of the soup, the whole grid if `None`.
+    pub size: Option<[u32; 2]>,
+    /// Bottom left corner of the soup in the grid, centring it if `None`.
+    pub corner: Option<[u32; 2]>,
+    pub symmetry: Symmetry,
+}
+
+impl Soup {
+    /// A soup of the whole grid from a seed of its own.
+    pub fn random() -> Soup {
+        Soup {
+            seed: rand::random(),
+            density: 0.4,
+            size: None,
+            corner: None,
+            symmetry: Symmetry::C1,
+        }
+    }
+
+    /// Checks that the soup fits a `grid_size` grid.
+    pub fn check(&self, grid_size: [u32; 2]) -> Result<()> {
+        let density = self.density;
+        ensure!(
+            (0.0..=1.0).contains(&density),
+            "the density is a chance between 0 and 1, got {density}"
+        );
+        let [width, height] = self.size(grid_size);
+        let [x, y] = self.corner(grid_size);
+        let [grid_width, grid_height] = grid_size;
+        ensure!(
+            (width > 0 && width <= grid_width && x <= grid_width - width)
+                && (height > 0 && height <= grid_height && y <= grid_height - height),
+            "a {width}x{height} soup at {x},{y} does not fit a {grid_width}x{grid_height} grid"
+        );
+        ensure!(
+            !self.symmetry.needs_square() || width == height,
+            "{:?} symmetry needs a square soup, got {width}x{height}",
+            self.symmetry
+        );
+        Ok(())
+    }
+
+    fn size(&self, grid_size: [u32; 2]) -> [u32; 2] {
+        self.size.unwrap_or(grid_size)
+    }
+
+    fn corner(&self, grid_size: [u32; 2]) -> [u32; 2] {
+        let size = self.size(grid_size);
+        self.corner
+            .unwrap_or([0, 1].map(|axis| grid_size[axis].saturating_sub(size[axis]) / 2))
+    }
+
+    /// The cells of a `grid_size` grid holding the soup, row by row from the bottom. Live cells
+    /// take any of the states from 1 to `live_states`.
+    pub fn cells(&self, grid_size: [u32; 2], live_states: u32) -> Vec<u32> {
+        let [width, height] = self.size(grid_size);
+        let mut rng = Rng::new(self.seed);
+        let random: Vec<u32> = (0..width * height)
+            .map(|_| {
+                let alive = rng.chance(self.density);
+                let state = 1 + rng.below(live_states);
+                u32::from(alive) * state
+            })
+            .collect();
+
+        let [grid_width, grid_height] = grid_size;
+        let [left, bottom] = self.corner(grid_size);
+        let mut cells = vec![0; (grid_width * grid_height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                // Every cell of an orbit takes the state drawn for the first cell of it.
+                let orbit = self.symmetry.orbit([width, height], [x, y]);
+                let first = orbit.iter().map(|&[x, y]| y * width + x).min().unwrap();
+                cells[((bottom + y) * grid_width + left + x) as usize] = random[first as usize];
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rng, Soup, Symmetry};
+
+    #[test]
+    fn seeds_give_the_same_numbers_everywhere() {
+        // SplitMix64's published outputs for seed 0.
+        let mut rng = Rng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+    }
+
+    #[test]
+    fn soups_are_placed_and_symmetric() {
+        let soup = |symmetry| Soup {
+            seed: 42,
+            density: 0.5,
+            size: Some([16, 16]),
+            corner: None,
+            symmetry,
+        };
+        let grid = [64, 48];
+        let cells = soup(Symmetry::C1).cells(grid, 1);
+        assert_eq!(cells, soup(Symmetry::C1).cells(grid, 1));
+        assert_ne!(
+            cells,
+            Soup {
+                seed: 43,
+                ..soup(Symmetry::C1)
+            }
+            .cells(grid, 1)
+        );
+        // Centred: nothing outside the 16x16 square at 24,16.
+        for (i, &cell) in cells.iter().enumerate() {
+            let [x, y] = [i % 64, i / 64];
+            if !((24..40).contains(&x) && (16..32).contains(&y)) {
+                assert_eq!(cell, 0, "{x},{y}");
+            }
+        }
+        let population = cells.iter().sum::<u32>();
+        assert!((64..192).contains(&population), "{population}");
+
+        let at = |cells: &[u32], [x, y]: [usize; 2]| cells[(16 + y) * 64 + 24 + x];
+        for symmetry in [
+            Symmetry::C2,
+            Symmetry::C4,
+            Symmetry::D2,
+            Symmetry::D4,
+            Symmetry::D8,
+        ] {
+            let cells = soup(symmetry).cells(grid, 1);
+            for y in 0..16 {
+                for x in 0..16 {
+                    let cell = at(&cells, [x, y]);
+                    let half_turn = at(&cells, [15 - x, 15 - y]);
+                    let quarter_turn = at(&cells, [15 - y, x]);
+                    let mirror = at(&cells, [15 - x, y]);
+                    let diagonal = at(&cells, [y, x]);
+                    match symmetry {
+                        Symmetry::C2 => assert_eq!(cell, half_turn),
+                        Symmetry::C4 => assert_eq!(cell, quarter_turn),
+                        Symmetry::D2 => assert_eq!(cell, mirror),
+                        Symmetry::D4 => assert_eq!([cell; 2], [mirror, half_turn]),
+                        _ => assert_eq!([cell; 3], [quarter_turn, mirror, diagonal]),
+                    }
+                }
+            }
+        }
+        // Without symmetry, halves differ.
+        let cells = soup(Symmetry::C1).cells(grid, 1);
+        assert!((0..16).any(|y| (0..16).any(|x| at(&cells, [x, y]) != at(&cells, [15 - x, y]))));
+    }
+
+    #[test]
+    fn soups_must_fit_the_grid() {
+        let soup = |size, corner| Soup {
+            seed: 0,
+            density: 0.5,
+            size,
+            corner,
+            symmetry: Symmetry::C1,
+        };
+        let grid = [16, 16];
+        assert!(soup(None, None).check(grid).is_ok());
+        assert!(soup(Some([4, 8]), Some([12, 8])).check(grid).is_ok());
+        for (size, corner) in [
+            (Some([17, 4]), None),
+            (Some([0, 4]), None),
+            (Some([4, 4]), Some([13, 0])),
+            (Some([4, 4]), Some([0, 13])),
+            (None, Some([1, 0])),
+            // Corners far enough out to wrap around when added to the size.
+            (None, Some([u32::MAX, 0])),
+            (Some([4, 4]), Some([0, u32::MAX - 3])),
+        ] {
+            assert!(
+                soup(size, corner).check(grid).is_err(),
+                "{size:?} {corner:?}"
+            );
+        }
+        let square = Soup {
+            symmetry: Symmetry::D8,
+            ..soup(Some([4, 8]), None)
+        };
+        assert!(square.check(grid).is_err());
+    }
+}
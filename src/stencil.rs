@@ -0,0 +1,163 @@
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::rule::Rule;
+
+/// A weighted neighbourhood: the neighbour count of a cell is the sum of the weights of the live
+/// cells at the stencil's offsets, so totalistic rules can be evaluated over any neighbourhood.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stencil {
+    /// `(dx, dy, weight)` with `dy` pointing up the screen; zero weights are left out.
+    pub entries: Vec<[i32; 3]>,
+}
+
+impl Stencil {
+    /// Largest distance of an offset from the centre cell.
+    pub fn radius(&self) -> u32 {
+        self.entries
+            .iter()
+            .map(|&[dx, dy, _]| dx.unsigned_abs().max(dy.unsigned_abs()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn from_offsets(offsets: &[(i32, i32)]) -> Stencil {
+        Stencil {
+            entries: offsets.iter().map(|&(dx, dy)| [dx, dy, 1]).collect(),
+        }
+    }
+
+    /// Parses a square weight matrix with an odd number of rows, e.g. `0,1,0;1,0,1;0,1,0`. Rows
+    /// are separated by `;` and listed top to bottom; the centre entry is the cell itself.
+    fn parse_matrix(matrix: &str) -> Result<Stencil> {
+        let rows = matrix
+            .split(';')
+            .map(|row| {
+                row.split(',')
+                    .map(|weight| {
+                        weight
+                            .trim()
+                            .parse::<i32>()
+                            .with_context(|| format!("invalid stencil weight {weight:?}"))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let size = rows.len();
+        ensure!(
+            size % 2 == 1 && rows.iter().all(|row| row.len() == size),
+            "a stencil must be a square matrix with an odd number of rows, got {matrix:?}"
+        );
+        let radius = (size / 2) as i32;
+        ensure!(
+            radius as u32 <= Rule::MAX_RADIUS,
+            "the stencil radius must be at most {}",
+            Rule::MAX_RADIUS
+        );
+
+        let mut entries = Vec::new();
+        for (row, weights) in rows.iter().enumerate() {
+            for (column, &weight) in weights.iter().enumerate() {
+                if weight != 0 {
+                    entries.push([column as i32 - radius, radius - row as i32, weight]);
+                }
+            }
+        }
+        ensure!(
+            !entries.is_empty(),
+            "a stencil needs at least one nonzero weight, got {matrix:?}"
+        );
+        Ok(Stencil { entries })
+    }
+}
+
+impl FromStr for Stencil {
+    type Err = anyhow::Error;
+
+    /// Accepts the names of the built-in stencils or a weight matrix.
+    fn from_str(stencil: &str) -> Result<Stencil> {
+        let stencil = match stencil.trim() {
+            "moore" => Stencil::from_offsets(&[
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+                (-1, 0),
+                (1, 0),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+            ]),
+            "vonneumann" => Stencil::from_offsets(&[(0, 1), (-1, 0), (1, 0), (0, -1)]),
+            // The hexagonal neighbourhood on a square grid, skewed the same way Golly does it.
+            "hex" => Stencil::from_offsets(&[(-1, 1), (0, 1), (-1, 0), (1, 0), (0, -1), (1, -1)]),
+            "knight" => Stencil::from_offsets(&[
+                (-2, 1),
+                (-1, 2),
+                (1, 2),
+                (2, 1),
+                (2, -1),
+                (1, -2),
+                (-1, -2),
+                (-2, -1),
+            ]),
+            matrix if matrix.contains([',', ';']) || matrix.parse::<i32>().is_ok() => {
+                Stencil::parse_matrix(matrix)?
+            }
+            name => bail!("unknown stencil {name:?}, expected moore, vonneumann, hex, knight or a weight matrix"),
+        };
+        Ok(stencil)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stencil;
+
+    #[test]
+    fn parses_named_stencils_and_weight_matrices() {
+        let parse = |stencil: &str| stencil.parse::<Stencil>().unwrap();
+        for (name, size, radius) in [
+            ("moore", 8, 1),
+            ("vonneumann", 4, 1),
+            ("hex", 6, 1),
+            ("knight", 8, 2),
+        ] {
+            let stencil = parse(name);
+            assert_eq!(stencil.entries.len(), size, "{name}");
+            assert_eq!(stencil.radius(), radius, "{name}");
+            assert!(
+                stencil.entries.iter().all(|&[_, _, weight]| weight == 1),
+                "{name}"
+            );
+        }
+
+        // Rows go top to bottom, and zero weights are left out.
+        assert_eq!(
+            parse("0,2,0; 1,0,-1; 0,3,0").entries,
+            [[0, 1, 2], [-1, 0, 1], [1, 0, -1], [0, -1, 3]]
+        );
+        assert_eq!(parse("5").entries, [[0, 0, 5]]);
+        assert_eq!(
+            parse("1,0,0,0,0;0,0,0,0,0;0,0,0,0,0;0,0,0,0,0;0,0,0,0,0").radius(),
+            2
+        );
+
+        for invalid in [
+            // Even sized.
+            "1,1;1,1",
+            // Not square.
+            "1,1,1;1,1,1",
+            "1,1,1;1,1;1,1,1",
+            // Nothing to count.
+            "0",
+            "0,0,0;0,0,0;0,0,0",
+            "1,x,1;1,0,1;1,1,1",
+            "1,,1;1,0,1;1,1,1",
+            "circle",
+        ] {
+            assert!(invalid.parse::<Stencil>().is_err(), "{invalid:?}");
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+/// The lattice the cells are laid out on.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    #[default]
+    Square,
+    /// Hexagons in offset coordinates: odd rows are shifted right by half a cell, so each cell
+    /// has two neighbours in its own row and two in each of the rows above and below.
+    Hexagonal,
+    /// Triangles alternately pointing up and down: the cell at `(x, y)` points up when `x + y` is
+    /// even. Every triangle touches twelve others at its corners.
+    TriangularVertex,
+    /// The triangular lattice with only the three triangles sharing an edge as neighbours.
+    TriangularEdge,
+}
+
+impl Topology {
+    /// Number of vertices the display shader emits for each cell.
+    pub fn vertices_per_cell(self) -> u32 {
+        match self {
+            Topology::Square => 6,
+            Topology::Hexagonal => 18,
+            Topology::TriangularVertex | Topology::TriangularEdge => 3,
+        }
+    }
+
+    /// Whether the lattice lines up with itself across the edges of a `[width, height]` grid:
+    /// hexagon rows alternate between shifted and unshifted, and triangles between pointing up and
+    /// down in both directions.
+    pub fn wraps(self, [width, height]: [u32; 2]) -> bool {
+        match self {
+            Topology::Square => true,
+            Topology::Hexagonal => height.is_multiple_of(2),
+            Topology::TriangularVertex | Topology::TriangularEdge => {
+                width.is_multiple_of(2) && height.is_multiple_of(2)
+            }
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = anyhow::Error;
+
+    fn from_str(topology: &str) -> Result<Topology> {
+        Ok(match topology {
+            "square" => Topology::Square,
+            "hex" | "hexagonal" => Topology::Hexagonal,
+            "tri" | "triangular" => Topology::TriangularVertex,
+            "tri-edge" | "triangular-edge" => Topology::TriangularEdge,
+            _ => bail!(
+                "unknown topology {topology:?}, expected square, hex, triangular or triangular-edge"
+            ),
+        })
+    }
+}